@@ -0,0 +1,165 @@
+//! Many inputs committed under one Merkle root.
+//!
+//! Each input is committed on its own, together with its position, with a pepper derived
+//! from a leaf key, and the leaf hashes of those commitments form a Merkle tree whose root
+//! is committed to like any typed input. Only the one commitment is published. Any input
+//! can later be revealed with its pepper and authentication path, without exposing the others.
+
+use crate::memory::SecretString;
+use crate::merkle::{self, MerkleProof, MerkleTree};
+use crate::{pepper, CommitError, CommitHasher, CommitParams, Commitment, Normalization, PepperOptions, Secret};
+use serde::{Deserialize, Serialize};
+use std::io::{self, ErrorKind};
+use zeroize::Zeroizing;
+
+/// Separates leaf peppers from every other HMAC keyed in the client
+const LEAF_PEPPER_INFO: &[u8] = b"concoin-batch-leaf-v1";
+
+/// The inputs of a batch, recorded with the secret so any of them can be revealed later
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchManifest {
+    /// Hex-encoded key the leaf peppers are derived from, wiped on drop
+    pub leaf_key: SecretString,
+    /// Hex-encoded inputs in leaf order, already normalized, wiped on drop
+    pub inputs: Vec<SecretString>,
+}
+
+/// Everything needed to check that one input was part of a committed batch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchProof {
+    /// Hex-encoded input, as it was hashed
+    pub input: String,
+    #[serde(flatten)]
+    pub merkle: MerkleProof,
+}
+
+fn leaf_pepper(leaf_key: &[u8], index: u64) -> Zeroizing<Vec<u8>> {
+    merkle::leaf_pepper(leaf_key, LEAF_PEPPER_INFO, &[&index.to_be_bytes()])
+}
+
+/// Merkle leaf of input `index`: the leaf hash of its peppered commitment to the index
+/// followed by the input
+fn leaf(params: &CommitParams, pepper: &[u8], index: u64, input: &[u8]) -> Vec<u8> {
+    let mut hasher = CommitHasher::new(params, pepper);
+    hasher.update(&index.to_be_bytes());
+    hasher.update(input);
+    merkle::leaf_hash(params.algorithm, &hasher.finalize_bytes())
+}
+
+fn tree(params: &CommitParams, leaf_key: &[u8], inputs: &[&[u8]]) -> Option<MerkleTree> {
+    let leaves = (0u64..)
+        .zip(inputs)
+        .map(|(index, input)| leaf(params, &leaf_pepper(leaf_key, index), index, input))
+        .collect();
+    MerkleTree::new(params.algorithm, leaves)
+}
+
+/// The inputs are normalized as `params` asks up front. The leaves and the root are
+/// hashed raw, so this is the only place normalization happens.
+fn raw(params: &CommitParams) -> CommitParams {
+    CommitParams {
+        normalization: Normalization::Raw,
+        ..params.clone()
+    }
+}
+
+/// Commits to every input under one Merkle root, drawing the root pepper and leaf key as `options` asks
+pub fn commit_batch<I: AsRef<[u8]>>(
+    params: &CommitParams,
+    options: &PepperOptions,
+    inputs: &[I],
+) -> Result<(Commitment, Secret, BatchManifest), CommitError> {
+    params.check_pepper_len(options.len)?;
+    if inputs.is_empty() {
+        return Err(CommitError::EmptyBatch);
+    }
+    let inputs: Vec<Zeroizing<Vec<u8>>> = inputs
+        .iter()
+        .map(|input| params.normalization.apply(input.as_ref()))
+        .collect();
+    let slices: Vec<&[u8]> = inputs.iter().map(|input| input.as_slice()).collect();
+    let raw = raw(params);
+    let (root_pepper, mix) = pepper::generate(options)?;
+    let leaf_key = Zeroizing::new(pepper::os_random(options.len)?);
+    let tree = tree(&raw, &leaf_key, &slices).ok_or(CommitError::EmptyBatch)?;
+
+    let secret = Secret::new(root_pepper, tree.root()).with_mix(mix);
+    let manifest = BatchManifest {
+        leaf_key: SecretString::new(hex::encode(&*leaf_key)),
+        inputs: slices.iter().map(|input| SecretString::new(hex::encode(input))).collect(),
+    };
+    Ok((secret.commitment(&raw), secret, manifest))
+}
+
+impl BatchManifest {
+    /// A proof for every input, in leaf order. `secret` is the one the root was committed with.
+    pub fn proofs(&self, params: &CommitParams, secret: &Secret) -> Result<Vec<BatchProof>, hex::FromHexError> {
+        let leaf_key = Zeroizing::new(hex::decode(&*self.leaf_key)?);
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| hex::decode(&**input).map(Zeroizing::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        let slices: Vec<&[u8]> = inputs.iter().map(|input| input.as_slice()).collect();
+        let Some(tree) = tree(&raw(params), &leaf_key, &slices) else {
+            return Ok(Vec::new());
+        };
+
+        Ok((0u64..)
+            .zip(&self.inputs)
+            .map(|(index, input)| BatchProof {
+                input: input.to_string(),
+                merkle: tree.merkle_proof(index as usize, &leaf_pepper(&leaf_key, index), secret),
+            })
+            .collect())
+    }
+}
+
+/// Checks that the input `proof` reveals sits at its index in the batch behind `commitment`
+pub fn verify_batch_proof(commitment: &Commitment, proof: &BatchProof) -> io::Result<bool> {
+    let leaf_pepper = proof.merkle.decode_leaf_pepper()?;
+    let input = Zeroizing::new(
+        hex::decode(&proof.input)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, format!("invalid proof: {e}")))?,
+    );
+    let params = raw(commitment.params());
+    let leaf = leaf(&params, &leaf_pepper, proof.merkle.index, &input);
+    proof.merkle.opens(commitment, &params, &leaf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CommitScheme;
+
+    fn options(len: usize) -> PepperOptions {
+        PepperOptions {
+            len,
+            ..PepperOptions::default()
+        }
+    }
+
+    #[test]
+    fn concat_batches_refuse_other_pepper_lengths() {
+        let result = commit_batch(&CommitParams::default(), &options(48), &["a", "b"]);
+        assert!(matches!(result, Err(CommitError::ConcatPepperLength(48))));
+    }
+
+    #[test]
+    fn every_input_proves_against_the_root() {
+        let params = CommitParams {
+            scheme: CommitScheme::Hmac,
+            ..CommitParams::default()
+        };
+        let (commitment, secret, manifest) = commit_batch(&params, &options(48), &["a", "b", "c"]).unwrap();
+        let proofs = manifest.proofs(&params, &secret).unwrap();
+        assert_eq!(proofs.len(), 3);
+        for proof in &proofs {
+            assert!(verify_batch_proof(&commitment, proof).unwrap());
+        }
+
+        let mut moved = proofs[0].clone();
+        moved.merkle.index = 1;
+        assert!(!verify_batch_proof(&commitment, &moved).unwrap_or(false));
+    }
+}
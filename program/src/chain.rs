@@ -0,0 +1,171 @@
+//! Hash chains for sequential reveals, as in S/KEY and RANDAO.
+//!
+//! A private seed is hashed over and over with SHA-512 and only the last value, the tip,
+//! is published. Each round reveals the link one step closer to the seed. Anyone holding
+//! the previous link, or the tip for the first round, checks a new link by hashing it once.
+//! The seed itself is never revealed: the last link is its first hash.
+
+use crate::{
+    pepper, CommitError, CommitParams, CommitScheme, Commitment, HashAlgorithm, Normalization, PepperOptions,
+    Secret,
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// Most links a chain can have, so computing a link stays quick
+pub const MAX_CHAIN_LEN: u64 = 1_000_000;
+
+const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha512;
+
+/// How long a chain is and how many of its links have been revealed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainPosition {
+    pub length: u64,
+    pub revealed: u64,
+}
+
+impl ChainPosition {
+    pub fn remaining(&self) -> u64 {
+        self.length.saturating_sub(self.revealed)
+    }
+
+    /// Rejects a position no chain could be at, e.g. one edited on disk
+    pub fn check(&self) -> Result<(), CommitError> {
+        if !(1..=MAX_CHAIN_LEN).contains(&self.length) {
+            return Err(CommitError::ChainLength(self.length));
+        }
+        if self.revealed > self.length {
+            return Err(CommitError::ChainRevealed {
+                length: self.length,
+                revealed: self.revealed,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Parameters a chain tip is recorded under: plain SHA-512 of the previous link
+pub fn params() -> CommitParams {
+    CommitParams {
+        algorithm: ALGORITHM,
+        scheme: CommitScheme::Concat,
+        context: None,
+        normalization: Normalization::Raw,
+    }
+}
+
+/// `start` hashed `steps` times
+fn hash_forward(start: &[u8], steps: u64) -> Zeroizing<Vec<u8>> {
+    let mut link = Zeroizing::new(start.to_vec());
+    for _ in 0..steps {
+        link = Zeroizing::new(ALGORITHM.digest(&[&link]));
+    }
+    link
+}
+
+/// A seed and how far its chain has been revealed. The seed is the pepper of `secret`.
+#[derive(Debug)]
+pub struct HashChain {
+    secret: Secret,
+    position: ChainPosition,
+}
+
+impl HashChain {
+    /// Draws a seed as `options` asks for a chain with `length` links, none revealed yet
+    pub fn generate(options: &PepperOptions, length: u64) -> Result<Self, CommitError> {
+        let position = ChainPosition { length, revealed: 0 };
+        position.check()?;
+        let (seed, mix) = pepper::generate(options)?;
+        Self::new(Secret::new(seed, Vec::new()).with_mix(mix), position)
+    }
+
+    /// Fails if no chain could be at `position`, so links are only ever computed for valid ones
+    pub fn new(secret: Secret, position: ChainPosition) -> Result<Self, CommitError> {
+        position.check()?;
+        Ok(Self { secret, position })
+    }
+
+    pub fn secret(&self) -> &Secret {
+        &self.secret
+    }
+
+    pub fn position(&self) -> ChainPosition {
+        self.position
+    }
+
+    /// Link `index`, counting the tip as 0, which is the seed hashed `length + 1 - index` times
+    fn link(&self, index: u64) -> Zeroizing<Vec<u8>> {
+        hash_forward(self.secret.pepper(), self.position.length + 1 - index)
+    }
+
+    /// The published anchor of the chain
+    pub fn tip(&self) -> Commitment {
+        Commitment::from_hex(params(), &hex::encode(&*self.link(0)))
+    }
+
+    /// Moves one link towards the seed and returns it, or None once every link is revealed.
+    /// Record the new position before publishing the link.
+    pub fn reveal_next(&mut self) -> Option<Zeroizing<Vec<u8>>> {
+        if self.position.remaining() == 0 {
+            return None;
+        }
+        self.position.revealed += 1;
+        Some(self.link(self.position.revealed))
+    }
+}
+
+/// Checks that `link` comes right after `previous`, the tip or the last revealed link
+pub fn verify_link(previous: &[u8], link: &[u8]) -> bool {
+    verify_links(previous, link, 1)
+}
+
+/// Checks that `link` comes `steps` links after `anchor`, for a verifier who missed some rounds
+pub fn verify_links(anchor: &[u8], link: &[u8], steps: u64) -> bool {
+    steps <= MAX_CHAIN_LEN && *hash_forward(link, steps) == anchor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: [u8; 32] = [7; 32];
+
+    fn chain(length: u64) -> HashChain {
+        HashChain::new(Secret::new(SEED.to_vec(), Vec::new()), ChainPosition { length, revealed: 0 }).unwrap()
+    }
+
+    #[test]
+    fn each_link_follows_the_one_before() {
+        let mut chain = chain(5);
+        let tip = hex::decode(chain.tip().as_hex()).unwrap();
+        let mut previous = Zeroizing::new(tip.clone());
+        for revealed in 1..=5 {
+            let link = chain.reveal_next().unwrap();
+            assert!(verify_link(&previous, &link));
+            assert!(verify_links(&tip, &link, revealed));
+            assert!(!verify_links(&tip, &link, revealed + 1));
+            assert_eq!(chain.position().revealed, revealed);
+            previous = link;
+        }
+        assert!(!verify_link(&tip, &previous));
+    }
+
+    #[test]
+    fn the_last_link_hashes_the_seed_and_then_the_chain_ends() {
+        let mut chain = chain(3);
+        let links: Vec<_> = std::iter::from_fn(|| chain.reveal_next()).collect();
+        assert_eq!(links.len(), 3);
+        assert_eq!(*links[2], ALGORITHM.digest(&[&SEED]));
+        assert!(links.iter().all(|link| **link != SEED));
+        assert!(chain.reveal_next().is_none());
+        assert_eq!(chain.position().remaining(), 0);
+    }
+
+    #[test]
+    fn impossible_positions_are_refused() {
+        let secret = || Secret::new(SEED.to_vec(), Vec::new());
+        assert!(HashChain::new(secret(), ChainPosition { length: 0, revealed: 0 }).is_err());
+        assert!(HashChain::new(secret(), ChainPosition { length: MAX_CHAIN_LEN + 1, revealed: 0 }).is_err());
+        assert!(HashChain::new(secret(), ChainPosition { length: 2, revealed: 3 }).is_err());
+    }
+}
@@ -0,0 +1,658 @@
+use clap::{Args, Parser, Subcommand};
+use color_eyre::{eyre::eyre, Result};
+use program::store::{self, SecretRecord};
+use program::context::DEFAULT_PROTOCOL;
+use program::batch::{self, BatchProof};
+use program::chain::{self, HashChain};
+use program::directory::{self, InclusionProof};
+use program::file;
+use program::journal;
+use program::pedersen::{self, Opening, PedersenCommitment, PedersenInfo};
+use program::pepper::{MAX_PEPPER_LEN, MIN_PEPPER_LEN};
+use program::range::{self, CommitmentBundle};
+use program::seed::{self, MasterSeed};
+use program::{
+    commit_with_options, health, kat, verify, CommitParams, CommitScheme, Commitment, Context, HashAlgorithm,
+    Normalization, PepperOptions, Secret, PEPPER_LEN,
+};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use zeroize::Zeroizing;
+
+/// Client for ConCoin commitments. Starts the interactive editor when no command is given.
+#[derive(Parser)]
+#[command(version)]
+pub struct Cli {
+    /// Secrets file to read from and write to
+    #[arg(long, global = true, default_value = store::DEFAULT_PATH)]
+    pub file: String,
+
+    /// Passphrase for an encrypted secrets file. New secrets are saved encrypted when it is set
+    #[arg(long, global = true, env = "CONCOIN_PASSPHRASE", hide_env_values = true)]
+    pub passphrase: Option<String>,
+
+    /// Start the editor with the input drawn as bullets
+    #[arg(long)]
+    pub masked: bool,
+
+    #[command(flatten)]
+    pub params: ParamsArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// How commitments are made and checked, shared by the editor and the commands
+#[derive(Args)]
+pub struct ParamsArgs {
+    /// sha512, sha3-512, blake3, sha256 or keccak256
+    #[arg(long, global = true, default_value_t)]
+    pub algorithm: HashAlgorithm,
+
+    /// concat hashes pepper then input, hmac keys an HMAC of the input with the pepper
+    #[arg(long, global = true, default_value_t)]
+    pub scheme: CommitScheme,
+
+    /// nfc composes text inputs before hashing so differently typed accents match, none hashes the bytes as given
+    #[arg(long, global = true, default_value_t)]
+    pub normalization: Normalization,
+
+    /// Round to bind commitments to. Without it commitments carry no context
+    #[arg(long, global = true)]
+    pub round: Option<String>,
+
+    /// Participant to bind commitments to, together with the round
+    #[arg(long, global = true, requires = "round", default_value = "")]
+    pub participant: String,
+
+    /// Protocol name bound into the context
+    #[arg(long, global = true, requires = "round", default_value = DEFAULT_PROTOCOL)]
+    pub protocol: String,
+
+    /// Protocol version bound into the context
+    #[arg(long, global = true, requires = "round", default_value_t = 1)]
+    pub protocol_version: u32,
+
+    /// Bytes of pepper in new secrets and in the secret hex given to verify, 16 to 64.
+    /// Only the hmac scheme takes other lengths than 32
+    #[arg(long, global = true, default_value_t = PEPPER_LEN, value_parser = parse_pepper_len)]
+    pub pepper_len: usize,
+}
+
+fn parse_pepper_len(value: &str) -> Result<usize, String> {
+    let len: usize = value.parse().map_err(|e| format!("{e}"))?;
+    if (MIN_PEPPER_LEN..=MAX_PEPPER_LEN).contains(&len) {
+        Ok(len)
+    } else {
+        Err(format!("must be between {MIN_PEPPER_LEN} and {MAX_PEPPER_LEN}"))
+    }
+}
+
+impl ParamsArgs {
+    pub fn params(&self) -> CommitParams {
+        CommitParams {
+            algorithm: self.algorithm,
+            scheme: self.scheme,
+            context: self.round.as_ref().map(|round| Context {
+                protocol: self.protocol.clone(),
+                version: self.protocol_version,
+                round: round.clone(),
+                participant: self.participant.clone(),
+            }),
+            normalization: self.normalization,
+        }
+    }
+
+    pub fn pepper_options(&self) -> PepperOptions {
+        PepperOptions {
+            len: self.pepper_len,
+            user_entropy: None,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Commit to an input or a file, print its hash and store the secret
+    Commit {
+        #[arg(required_unless_present_any = ["path", "dir", "batch"], conflicts_with_all = ["path", "dir", "batch"])]
+        input: Option<String>,
+        /// Commit to the contents of this file instead, streamed from disk
+        #[arg(long)]
+        path: Option<PathBuf>,
+        /// Commit to every file under this directory with one Merkle root
+        #[arg(long, conflicts_with_all = ["path", "derive"])]
+        dir: Option<PathBuf>,
+        /// Commit to every line of this file, or of stdin for -, under one Merkle root
+        #[arg(long, conflicts_with_all = ["path", "dir", "derive"])]
+        batch: Option<PathBuf>,
+        /// Note stored alongside the secret
+        #[arg(long)]
+        label: Option<String>,
+        /// Your own randomness, e.g. dice rolls, mixed with the OS RNG into the pepper
+        #[arg(long, conflicts_with = "derive")]
+        entropy: Option<String>,
+        /// Derive the pepper from the master seed and the label instead of drawing it at random
+        #[arg(long)]
+        derive: bool,
+    },
+    /// Print the stored pepper+input hex for a commitment
+    Reveal {
+        /// Position in the secrets file or the commitment hash
+        target: String,
+    },
+    /// Print the inclusion proofs of a directory or batch commitment, as a JSON list unless one member is named
+    Prove {
+        /// Position in the secrets file or the commitment hash
+        target: String,
+        /// Path of one file relative to the committed directory, or position of one batch input
+        member: Option<String>,
+        /// Write each proof to <OUT>/<path or position>.proof.json instead
+        #[arg(long, conflicts_with = "member")]
+        out: Option<PathBuf>,
+    },
+    /// Check a revealed secret against a hash, exiting with 0 on a match, 1 on a mismatch and 2 if it can't be checked
+    Verify {
+        hash: String,
+        /// Pepper hex followed by input hex, or only the pepper hex with --path
+        #[arg(required_unless_present = "proof")]
+        secret: Option<String>,
+        /// File the commitment was made to
+        #[arg(long)]
+        path: Option<PathBuf>,
+        /// Proof from `prove`: of the file given with --path, or without it of a batch input
+        #[arg(long, conflicts_with = "secret")]
+        proof: Option<PathBuf>,
+    },
+    /// Manage the master seed derived peppers come from
+    Seed {
+        #[command(subcommand)]
+        command: SeedCommand,
+    },
+    /// Publish one anchor and reveal one link of a hash chain per round
+    Chain {
+        #[command(subcommand)]
+        command: ChainCommand,
+    },
+    /// Commit to numbers with Pedersen commitments, which can be added up and opened as a total
+    Pedersen {
+        #[command(subcommand)]
+        command: PedersenCommand,
+    },
+    /// Run the known-answer tests for every commitment scheme and the RNG startup health tests
+    Selftest,
+}
+
+#[derive(Subcommand)]
+pub enum SeedCommand {
+    /// Create the master seed and print its mnemonic. It is shown only this once
+    New,
+    /// Regenerate the secret of a derived commitment from the mnemonic, read from stdin
+    Restore {
+        hash: String,
+        input: String,
+        /// Label the commitment was made with
+        #[arg(long, default_value = "")]
+        label: String,
+        /// Highest counter to try
+        #[arg(long, default_value_t = 100_000)]
+        max_counter: u64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ChainCommand {
+    /// Create a chain with this many links, store its seed and print the tip to publish
+    New {
+        length: u64,
+        /// Note stored alongside the seed
+        #[arg(long)]
+        label: Option<String>,
+        /// Your own randomness, e.g. dice rolls, mixed with the OS RNG into the seed
+        #[arg(long)]
+        entropy: Option<String>,
+    },
+    /// Print the next link of a chain, once its new position is saved
+    Reveal {
+        /// Position in the secrets file or the tip
+        target: String,
+    },
+    /// Check a revealed link against the one before it or the tip, exiting with 0 on a match, 1 on a mismatch and 2 if it can't be checked
+    Verify {
+        previous: String,
+        link: String,
+        /// Links between the two, for rounds that were missed
+        #[arg(long, default_value_t = 1)]
+        steps: u64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PedersenCommand {
+    /// Commit to a number, print the commitment and store its opening
+    Commit {
+        value: u64,
+        /// Note stored alongside the opening
+        #[arg(long)]
+        label: Option<String>,
+    },
+    /// Add up published commitments, no openings needed
+    Add {
+        #[arg(required = true)]
+        commitments: Vec<String>,
+    },
+    /// Add up stored commitments, store the opening of their total and print it
+    Sum {
+        /// Positions in the secrets file or commitments
+        #[arg(required = true)]
+        targets: Vec<String>,
+        /// Note stored alongside the opening
+        #[arg(long)]
+        label: Option<String>,
+    },
+    /// Print the value and blinding hex that open a stored commitment
+    Open {
+        /// Position in the secrets file or the commitment
+        target: String,
+    },
+    /// Check an opening against a commitment, exiting with 0 on a match, 1 on a mismatch and 2 if it can't be checked
+    Verify {
+        commitment: String,
+        value: u64,
+        blinding: String,
+    },
+    /// Print the bundle to publish for a stored commitment, with a range proof when bounds are given
+    Bundle {
+        /// Position in the secrets file or the commitment
+        target: String,
+        /// Lowest value the range proof allows
+        #[arg(long, requires = "max")]
+        min: Option<u64>,
+        /// Highest value the range proof allows
+        #[arg(long, requires = "min")]
+        max: Option<u64>,
+        /// Write the bundle to this file instead
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Check the range proof in a bundle, exiting with 0 if it holds, 1 if it doesn't and 2 if it can't be checked
+    VerifyBundle {
+        bundle: PathBuf,
+    },
+}
+
+/// Finds a secret by its position in the secrets file or by its commitment hash
+fn find<'a>(secrets: &'a [SecretRecord], target: &str) -> Option<&'a SecretRecord> {
+    match target.parse::<usize>() {
+        Ok(index) if index < secrets.len() => secrets.get(index),
+        _ => {
+            let wanted = target.trim().to_ascii_lowercase();
+            secrets.iter().find(|record| record.commitment == wanted)
+        }
+    }
+}
+
+/// Exit code of a check that couldn't be made, e.g. over malformed hex, so scripts can tell it from a mismatch
+const CHECK_ERROR: u8 = 2;
+
+impl Command {
+    /// Whether the command answers with a [`verdict`]
+    fn is_check(&self) -> bool {
+        matches!(
+            self,
+            Self::Verify { .. }
+                | Self::Chain { command: ChainCommand::Verify { .. } }
+                | Self::Pedersen { command: PedersenCommand::Verify { .. } | PedersenCommand::VerifyBundle { .. } }
+        )
+    }
+}
+
+/// Prints the outcome of a check, which is also the exit code
+fn verdict(matches: bool) -> ExitCode {
+    if matches {
+        println!("match");
+        ExitCode::SUCCESS
+    } else {
+        println!("mismatch");
+        ExitCode::FAILURE
+    }
+}
+
+pub fn run(
+    command: Command,
+    file: &str,
+    passphrase: Option<&str>,
+    params: &CommitParams,
+    pepper_options: PepperOptions,
+) -> Result<ExitCode> {
+    let check = command.is_check();
+    match run_command(command, file, passphrase, params, pepper_options) {
+        Err(e) if check => {
+            eprintln!("Error: {e:?}");
+            Ok(ExitCode::from(CHECK_ERROR))
+        }
+        result => result,
+    }
+}
+
+fn run_command(
+    command: Command,
+    file: &str,
+    passphrase: Option<&str>,
+    params: &CommitParams,
+    pepper_options: PepperOptions,
+) -> Result<ExitCode> {
+    match command {
+        Command::Commit { input, path, dir, batch, label, entropy, derive } => {
+            let input = Zeroizing::new(input.unwrap_or_default());
+            let options = PepperOptions {
+                user_entropy: entropy.map(|entropy| Zeroizing::new(entropy.into_bytes())),
+                ..pepper_options
+            };
+            let record = if let Some(batch) = &batch {
+                let lines = if batch.as_os_str() == "-" {
+                    Zeroizing::new(std::io::read_to_string(std::io::stdin())?)
+                } else {
+                    Zeroizing::new(std::fs::read_to_string(batch)?)
+                };
+                let inputs: Vec<&str> = lines.lines().filter(|line| !line.is_empty()).collect();
+                let (commitment, secret, manifest) = batch::commit_batch(params, &options, &inputs)?;
+                eprintln!("Committed to {} inputs", inputs.len());
+                SecretRecord::new(&commitment, &secret, label).with_batch(Some(manifest))
+            } else if let Some(dir) = &dir {
+                let (commitment, secret, manifest) = directory::commit_directory(params, &options, dir)?;
+                SecretRecord::new(&commitment, &secret, label).with_tree(Some(manifest))
+            } else if derive {
+                let (seed, counter) = seed::next_counter(file, passphrase)?;
+                let derivation_label = label.as_deref().unwrap_or_default();
+                match &path {
+                    Some(path) => {
+                        let (commitment, secret, file_input) = file::commit_file_derived(
+                            params, &seed, counter, derivation_label, options.len, path,
+                        )?;
+                        SecretRecord::new(&commitment, &secret, label).with_file(Some(file_input))
+                    }
+                    None => {
+                        let (commitment, secret) = seed::commit_derived(
+                            params, &seed, counter, derivation_label, options.len, input.as_bytes(),
+                        )?;
+                        SecretRecord::new(&commitment, &secret, label)
+                    }
+                }
+            } else {
+                match &path {
+                    Some(path) => {
+                        let (commitment, secret, file_input) = file::commit_file(params, &options, path)?;
+                        SecretRecord::new(&commitment, &secret, label).with_file(Some(file_input))
+                    }
+                    None => {
+                        let (commitment, secret) = commit_with_options(params, &options, input.as_bytes())?;
+                        SecretRecord::new(&commitment, &secret, label)
+                    }
+                }
+            };
+
+            let commitment = record.commitment.clone();
+            store::merge_data_into_json(&[record], file, passphrase)?;
+
+            println!("{commitment}");
+        }
+        Command::Reveal { target } => {
+            let secrets = journal::load_with_secrets(file, passphrase)?;
+            let record = find(&secrets, &target).ok_or_else(|| eyre!("No secret in {file} matches {target}"))?;
+            if record.chain.is_some() {
+                return Err(eyre!("{target} is a hash chain, reveal its links one at a time with `chain reveal`"));
+            }
+            if record.pedersen.is_some() {
+                return Err(eyre!("{target} is a Pedersen commitment, open it with `pedersen open`"));
+            }
+
+            // A file's secret is only its pepper, the file itself is revealed alongside
+            if let Some(file_input) = &record.file {
+                eprintln!("File: {} ({} bytes)", file_input.path, file_input.size);
+            }
+            println!("{}", record.secret_hex().as_str());
+        }
+        Command::Prove { target, member, out } => {
+            let secrets = journal::load_with_secrets(file, passphrase)?;
+            let record = find(&secrets, &target).ok_or_else(|| eyre!("No secret in {file} matches {target}"))?;
+            let secret = record.secret()?;
+            // Each proof with the name it is asked for and written under
+            let proofs: Vec<(String, serde_json::Value)> = if let Some(manifest) = &record.tree {
+                manifest
+                    .proofs(record.hash_algorithm, &secret)?
+                    .into_iter()
+                    .map(|proof| Ok((proof.path.clone(), serde_json::to_value(proof)?)))
+                    .collect::<serde_json::Result<_>>()?
+            } else if let Some(manifest) = &record.batch {
+                manifest
+                    .proofs(&record.params(), &secret)?
+                    .into_iter()
+                    .map(|proof| Ok((proof.merkle.index.to_string(), serde_json::to_value(proof)?)))
+                    .collect::<serde_json::Result<_>>()?
+            } else {
+                return Err(eyre!("{target} is not a directory or batch commitment"));
+            };
+
+            if let Some(out) = out {
+                for (name, proof) in &proofs {
+                    let proof_path = out.join(format!("{name}.proof.json"));
+                    if let Some(parent) = proof_path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(&proof_path, serde_json::to_string_pretty(proof)?)?;
+                }
+                eprintln!("Wrote {} proofs to {}", proofs.len(), out.display());
+            } else if let Some(member) = member {
+                let (_, proof) = proofs
+                    .iter()
+                    .find(|(name, _)| *name == member)
+                    .ok_or_else(|| eyre!("{member} is not part of {target}"))?;
+                println!("{}", serde_json::to_string_pretty(proof)?);
+            } else {
+                let proofs: Vec<&serde_json::Value> = proofs.iter().map(|(_, proof)| proof).collect();
+                println!("{}", serde_json::to_string_pretty(&proofs)?);
+            }
+        }
+        Command::Verify { hash, proof: Some(proof), path: Some(path), .. } => {
+            let proof: InclusionProof = serde_json::from_str(&std::fs::read_to_string(&proof)?)?;
+            let commitment = Commitment::from_hex(params.clone(), &hash);
+            return Ok(verdict(directory::verify_inclusion(&commitment, &proof, &path)?));
+        }
+        Command::Verify { hash, proof: Some(proof), path: None, .. } => {
+            let proof: BatchProof = serde_json::from_str(&std::fs::read_to_string(&proof)?)?;
+            let commitment = Commitment::from_hex(params.clone(), &hash);
+            let matches = batch::verify_batch_proof(&commitment, &proof)?;
+            if matches {
+                let input = hex::decode(&proof.input)?;
+                eprintln!("Input {}: {}", proof.merkle.index, String::from_utf8_lossy(&input));
+            }
+            return Ok(verdict(matches));
+        }
+        Command::Verify { hash, secret: Some(secret), path: Some(path), .. } => {
+            let pepper = Zeroizing::new(
+                hex::decode(secret.trim()).map_err(|e| eyre!("Pepper is not valid hex: {e}"))?,
+            );
+            params.check_pepper_len(pepper.len())?;
+            let commitment = Commitment::from_hex(params.clone(), &hash);
+            return Ok(verdict(file::verify_file(&commitment, &pepper, &path)?));
+        }
+        Command::Verify { hash, secret: Some(secret), path: None, .. } => {
+            params.check_pepper_len(pepper_options.len)?;
+            let secret = Secret::from_hex_with_pepper_len(&secret, pepper_options.len)
+                .map_err(|e| eyre!("Secret is not valid pepper+input hex: {e}"))?;
+            return Ok(verdict(verify(&Commitment::from_hex(params.clone(), &hash), &secret)));
+        }
+        Command::Verify { secret: None, proof: None, .. } => unreachable!("clap requires a secret unless a proof is given"),
+        Command::Seed { command: SeedCommand::New } => {
+            let master_seed = MasterSeed::generate()?;
+            seed::save_new(file, &master_seed, passphrase)?;
+            println!("{}", master_seed.mnemonic().as_str());
+            eprintln!("Write these words down. They are the only way to regenerate derived peppers and won't be shown again.");
+        }
+        Command::Seed {
+            command: SeedCommand::Restore { hash, input, label, max_counter },
+        } => {
+            params.check_pepper_len(pepper_options.len)?;
+            eprintln!("Enter the mnemonic:");
+            // Room for any mnemonic up front, so reading it never leaves a copy in a freed buffer
+            let mut words = Zeroizing::new(String::with_capacity(1024));
+            std::io::stdin().read_line(&mut words)?;
+            let master_seed = MasterSeed::from_mnemonic(&words)?;
+
+            let commitment = Commitment::from_hex(params.clone(), &hash);
+            let secret = master_seed
+                .restore(&commitment, input.as_bytes(), &label, pepper_options.len, max_counter)
+                .ok_or_else(|| eyre!("No counter up to {max_counter} reproduces {hash} with this seed, input and label"))?;
+
+            let record = SecretRecord::new(&commitment, &secret, Some(label).filter(|label| !label.is_empty()));
+            store::merge_data_into_json(&[record], file, passphrase)?;
+            println!("{}", secret.to_hex().as_str());
+        }
+        Command::Chain { command: ChainCommand::New { length, label, entropy } } => {
+            let options = PepperOptions {
+                user_entropy: entropy.map(|entropy| Zeroizing::new(entropy.into_bytes())),
+                ..pepper_options
+            };
+            let chain = HashChain::generate(&options, length)?;
+            let record = SecretRecord::from_chain(&chain, label);
+            store::merge_data_into_json(&[record], file, passphrase)?;
+            println!("{}", chain.tip());
+        }
+        Command::Chain { command: ChainCommand::Reveal { target } } => {
+            let secrets = journal::load_with_secrets(file, passphrase)?;
+            let record = find(&secrets, &target).ok_or_else(|| eyre!("No secret in {file} matches {target}"))?;
+            let mut chain = record.hash_chain()?.ok_or_else(|| eyre!("{target} is not a hash chain"))?;
+            let link = chain.reveal_next().ok_or_else(|| eyre!("Every link of {target} has been revealed"))?;
+
+            // Save the new position first, so no link is ever handed out twice
+            let mut record = record.clone();
+            record.chain = Some(chain.position());
+            store::merge_data_into_json(&[record], file, passphrase)?;
+            let position = chain.position();
+            eprintln!("Link {} of {}", position.revealed, position.length);
+            println!("{}", hex::encode(&*link));
+        }
+        Command::Chain { command: ChainCommand::Verify { previous, link, steps } } => {
+            let previous = hex::decode(previous.trim()).map_err(|e| eyre!("Previous link is not valid hex: {e}"))?;
+            let link = hex::decode(link.trim()).map_err(|e| eyre!("Link is not valid hex: {e}"))?;
+            return Ok(verdict(chain::verify_links(&previous, &link, steps)));
+        }
+        Command::Pedersen { command: PedersenCommand::Commit { value, label } } => {
+            let (commitment, opening) = pedersen::commit(value)?;
+            let record = SecretRecord::from_pedersen(&opening, PedersenInfo::default(), params.context.clone(), label);
+            store::merge_data_into_json(&[record], file, passphrase)?;
+            println!("{commitment}");
+        }
+        Command::Pedersen { command: PedersenCommand::Add { commitments } } => {
+            let commitments = commitments
+                .iter()
+                .map(|commitment| PedersenCommitment::from_hex(commitment))
+                .collect::<Result<Vec<_>, _>>()?;
+            println!("{}", commitments.into_iter().sum::<PedersenCommitment>());
+        }
+        Command::Pedersen { command: PedersenCommand::Sum { targets, label } } => {
+            let secrets = journal::load_with_secrets(file, passphrase)?;
+            let mut openings = Vec::with_capacity(targets.len());
+            let mut parts = Vec::with_capacity(targets.len());
+            // The total is stored, and range proofs on it are bound, under the context the parts share
+            let mut context = None;
+            for target in &targets {
+                let record = find(&secrets, target).ok_or_else(|| eyre!("No secret in {file} matches {target}"))?;
+                let opening = record
+                    .pedersen_opening()?
+                    .ok_or_else(|| eyre!("{target} is not a Pedersen commitment"))?;
+                match &context {
+                    None => context = Some(record.context.clone()),
+                    Some(shared) if *shared != record.context => {
+                        return Err(eyre!("{target} was made for another round or participant than {}", targets[0]));
+                    }
+                    Some(_) => {}
+                }
+                parts.push(record.commitment.clone());
+                openings.push(opening);
+            }
+            let context = context.flatten();
+            if params.context.is_some() && context != params.context {
+                return Err(eyre!("The commitments were not made for the given round and participant"));
+            }
+
+            let total = Opening::aggregate(&openings)?;
+            let record = SecretRecord::from_pedersen(&total, PedersenInfo { parts }, context, label);
+            store::merge_data_into_json(&[record], file, passphrase)?;
+            println!("{}", total.commitment());
+        }
+        Command::Pedersen { command: PedersenCommand::Open { target } } => {
+            let secrets = journal::load_with_secrets(file, passphrase)?;
+            let record = find(&secrets, &target).ok_or_else(|| eyre!("No secret in {file} matches {target}"))?;
+            let opening = record
+                .pedersen_opening()?
+                .ok_or_else(|| eyre!("{target} is not a Pedersen commitment"))?;
+            println!("{} {}", opening.value(), hex::encode(opening.blinding().as_bytes()));
+        }
+        Command::Pedersen { command: PedersenCommand::Verify { commitment, value, blinding } } => {
+            let commitment = PedersenCommitment::from_hex(&commitment)?;
+            let opening = Opening::from_hex(value, &blinding)?;
+            return Ok(verdict(pedersen::verify(&commitment, &opening)));
+        }
+        Command::Pedersen { command: PedersenCommand::Bundle { target, min, max, out } } => {
+            let secrets = journal::load_with_secrets(file, passphrase)?;
+            let record = find(&secrets, &target).ok_or_else(|| eyre!("No secret in {file} matches {target}"))?;
+            let opening = record
+                .pedersen_opening()?
+                .ok_or_else(|| eyre!("{target} is not a Pedersen commitment"))?;
+            let context = record.context.clone();
+            let range_proof = match (min, max) {
+                (Some(min), Some(max)) => Some(range::prove(&opening, context.as_ref(), min, max)?),
+                _ => None,
+            };
+
+            let bundle = CommitmentBundle::new(&opening.commitment(), context, range_proof);
+            let bundle = serde_json::to_string_pretty(&bundle)?;
+            match out {
+                Some(out) => {
+                    std::fs::write(&out, bundle)?;
+                    eprintln!("Wrote the bundle to {}", out.display());
+                }
+                None => println!("{bundle}"),
+            }
+        }
+        Command::Pedersen { command: PedersenCommand::VerifyBundle { bundle } } => {
+            let bundle: CommitmentBundle = serde_json::from_str(&std::fs::read_to_string(&bundle)?)?;
+            if let Some(context) = &bundle.context {
+                eprintln!("Context: {context}");
+            }
+            if let Some(range_proof) = &bundle.range_proof {
+                eprintln!("Range: {}..={}", range_proof.min, range_proof.max);
+            }
+            // With --round given, a bundle made for another round or participant doesn't count
+            if params.context.is_some() && bundle.context != params.context {
+                eprintln!("The bundle was made for another context");
+                return Ok(verdict(false));
+            }
+            return Ok(verdict(bundle.verify()?));
+        }
+        Command::Selftest => {
+            match kat::run() {
+                Ok(passed) => {
+                    for name in passed {
+                        println!("ok {name}");
+                    }
+                }
+                Err(e) => {
+                    println!("{e}");
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+            match health::startup() {
+                Ok(()) => println!("ok RNG startup health tests"),
+                Err(e) => {
+                    println!("{e}");
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,184 @@
+//! Commitments to whole directory trees.
+//!
+//! Every file under the directory is hashed together with its relative path into a peppered
+//! leaf, each with its own pepper derived from a leaf key. The Merkle root over the leaves is
+//! then committed to like any typed input. An inclusion proof opens one file without saying
+//! anything about the others, and can be checked offline with just the proof and the file.
+
+use crate::memory::SecretString;
+use crate::merkle::{self, MerkleProof, MerkleTree};
+use crate::{
+    file, pepper, CommitError, CommitHasher, CommitParams, Commitment, HashAlgorithm, Normalization,
+    PepperOptions, Secret,
+};
+use serde::{Deserialize, Serialize};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Separates leaf peppers from every other HMAC keyed in the client
+const LEAF_PEPPER_INFO: &[u8] = b"concoin-tree-leaf-v1";
+
+/// What was committed for a directory, recorded with the secret so proofs can be made later
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeManifest {
+    /// Absolute path of the committed directory
+    pub root_dir: String,
+    /// Hex-encoded key the leaf peppers are derived from, wiped on drop
+    pub leaf_key: SecretString,
+    /// Every file in leaf order
+    pub files: Vec<TreeFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeFile {
+    /// Path relative to the committed directory, `/`-separated
+    pub path: String,
+    pub size: u64,
+    /// Hex-encoded Merkle leaf hash
+    pub leaf: String,
+}
+
+/// Everything needed to check that one file was part of a committed directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InclusionProof {
+    /// Path of the file relative to the committed directory
+    pub path: String,
+    #[serde(flatten)]
+    pub merkle: MerkleProof,
+}
+
+fn leaf_pepper(leaf_key: &[u8], path: &str) -> Zeroizing<Vec<u8>> {
+    let path_len = (path.len() as u64).to_be_bytes();
+    merkle::leaf_pepper(leaf_key, LEAF_PEPPER_INFO, &[&path_len, path.as_bytes()])
+}
+
+/// Merkle leaf of the file at `file`: the leaf hash of its peppered commitment to the length-prefixed
+/// relative path followed by the contents. Also gives the file's size.
+fn leaf(params: &CommitParams, pepper: &[u8], path: &str, file: &Path) -> io::Result<(Vec<u8>, u64)> {
+    let mut hasher = CommitHasher::new(params, pepper);
+    hasher.update(&(path.len() as u64).to_be_bytes());
+    hasher.update(path.as_bytes());
+    let size = file::stream(file, |chunk| hasher.update(chunk))?;
+    Ok((merkle::leaf_hash(params.algorithm, &hasher.finalize_bytes()), size))
+}
+
+/// Every regular file under `dir`, sorted by relative path. Symbolic links are not followed.
+fn walk(dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    fn visit(dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().into_string().map_err(|name| {
+                io::Error::new(ErrorKind::InvalidData, format!("file name {name:?} is not valid UTF-8"))
+            })?;
+            let relative = format!("{prefix}{name}");
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                visit(&entry.path(), &format!("{relative}/"), files)?;
+            } else if file_type.is_file() {
+                files.push((relative, entry.path()));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    visit(dir, "", &mut files)?;
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+/// Commits to every file under `dir` with a Merkle root, drawing the root pepper and
+/// leaf key as `options` asks
+pub fn commit_directory(
+    params: &CommitParams,
+    options: &PepperOptions,
+    dir: &Path,
+) -> Result<(Commitment, Secret, TreeManifest), CommitError> {
+    params.check_pepper_len(options.len)?;
+    // The root is raw hash output, never text
+    let params = CommitParams {
+        normalization: Normalization::Raw,
+        ..params.clone()
+    };
+    let (root_pepper, mix) = pepper::generate(options)?;
+    let leaf_key = Zeroizing::new(pepper::os_random(options.len)?);
+
+    let found = walk(dir).map_err(CommitError::Input)?;
+    let mut leaves = Vec::with_capacity(found.len());
+    let mut files = Vec::with_capacity(found.len());
+    for (path, full_path) in found {
+        let (leaf, size) =
+            leaf(&params, &leaf_pepper(&leaf_key, &path), &path, &full_path).map_err(CommitError::Input)?;
+        files.push(TreeFile {
+            path,
+            size,
+            leaf: hex::encode(&leaf),
+        });
+        leaves.push(leaf);
+    }
+    let tree = MerkleTree::new(params.algorithm, leaves).ok_or_else(|| {
+        CommitError::Input(io::Error::new(ErrorKind::InvalidInput, "the directory holds no files"))
+    })?;
+
+    let secret = Secret::new(root_pepper, tree.root()).with_mix(mix);
+    let root_dir = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let manifest = TreeManifest {
+        root_dir: root_dir.display().to_string(),
+        leaf_key: SecretString::new(hex::encode(&*leaf_key)),
+        files,
+    };
+    Ok((secret.commitment(&params), secret, manifest))
+}
+
+impl TreeManifest {
+    /// An inclusion proof for every file, in leaf order. `secret` is the one the root was committed with.
+    pub fn proofs(&self, algorithm: HashAlgorithm, secret: &Secret) -> Result<Vec<InclusionProof>, hex::FromHexError> {
+        let leaf_key = Zeroizing::new(hex::decode(&*self.leaf_key)?);
+        let leaves = self
+            .files
+            .iter()
+            .map(|file| hex::decode(&file.leaf))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some(tree) = MerkleTree::new(algorithm, leaves) else {
+            return Ok(Vec::new());
+        };
+
+        Ok(self
+            .files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| InclusionProof {
+                path: file.path.clone(),
+                merkle: tree.merkle_proof(index, &leaf_pepper(&leaf_key, &file.path), secret),
+            })
+            .collect())
+    }
+}
+
+/// Checks that `file`, under the path `proof` names, was part of the directory behind `commitment`
+pub fn verify_inclusion(commitment: &Commitment, proof: &InclusionProof, file: &Path) -> io::Result<bool> {
+    let leaf_pepper = proof.merkle.decode_leaf_pepper()?;
+    let params = CommitParams {
+        normalization: Normalization::Raw,
+        ..commitment.params().clone()
+    };
+    let (leaf, _) = leaf(&params, &leaf_pepper, &proof.path, file)?;
+    proof.merkle.opens(commitment, &params, &leaf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concat_directories_refuse_other_pepper_lengths() {
+        let options = PepperOptions {
+            len: 48,
+            ..PepperOptions::default()
+        };
+        // Refused before the directory is read, so it needn't exist
+        let result = commit_directory(&CommitParams::default(), &options, Path::new("missing"));
+        assert!(matches!(result, Err(CommitError::ConcatPepperLength(48))));
+    }
+}
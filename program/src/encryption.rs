@@ -0,0 +1,219 @@
+//! Passphrase encryption of the secrets file at rest.
+//!
+//! The key is derived from the passphrase with Argon2id and the file is sealed with
+//! XChaCha20-Poly1305. The KDF parameters, salt and nonce travel with the ciphertext
+//! so a file stays readable if the defaults change later. A [`DerivedKey`] seals many small
+//! messages, such as journal lines, under one derivation.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::rand_core::TryRngCore;
+use rand::rngs::OsRng;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroizing;
+
+pub const CIPHER: &str = "xchacha20poly1305";
+pub const KDF: &str = "argon2id";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+
+/// Binds the ciphertext to this container layout
+const ASSOCIATED_DATA: &[u8] = b"concoin-secrets-v1";
+
+#[derive(Debug)]
+pub enum EncryptionError {
+    /// The OS RNG could not provide a salt or nonce
+    Rng,
+    /// The stored KDF parameters are invalid or unsupported
+    Kdf(String),
+    /// The plaintext could not be sealed
+    Encrypt,
+    /// Decryption failed: wrong passphrase or a tampered file
+    Decrypt,
+    /// The container itself is malformed
+    Format(String),
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rng => f.write_str("OS RNG failed to provide secure random bytes"),
+            Self::Kdf(e) => write!(f, "key derivation failed: {e}"),
+            Self::Encrypt => f.write_str("encryption failed"),
+            Self::Decrypt => f.write_str("wrong passphrase or corrupted file"),
+            Self::Format(e) => write!(f, "malformed encrypted file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub algorithm: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    /// Hex-encoded salt
+    pub salt: String,
+}
+
+/// Encrypted form of a serialized secrets file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedContainer {
+    pub cipher: String,
+    pub kdf: KdfParams,
+    /// Hex-encoded nonce
+    pub nonce: String,
+    /// Hex-encoded ciphertext including the authentication tag
+    pub ciphertext: String,
+}
+
+/// A message sealed under a [`DerivedKey`], which carries its KDF parameters elsewhere
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedData {
+    pub cipher: String,
+    /// Hex-encoded nonce
+    pub nonce: String,
+    /// Hex-encoded ciphertext including the authentication tag
+    pub ciphertext: String,
+}
+
+/// The keys that mark a container. Everything else is skipped, so probing a plaintext
+/// document copies none of its secrets.
+#[derive(Deserialize)]
+struct Markers {
+    cipher: Option<IgnoredAny>,
+    ciphertext: Option<IgnoredAny>,
+}
+
+impl EncryptedContainer {
+    /// Tells an encrypted document apart from a plaintext one without decrypting it
+    pub fn detect(json: &[u8]) -> bool {
+        json.trim_ascii_start().starts_with(b"{")
+            && serde_json::from_slice::<Markers>(json)
+                .is_ok_and(|markers| markers.cipher.is_some() && markers.ciphertext.is_some())
+    }
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], EncryptionError> {
+    let mut bytes = [0u8; N];
+    OsRng
+        .try_fill_bytes(&mut bytes)
+        .map_err(|_| EncryptionError::Rng)?;
+    Ok(bytes)
+}
+
+/// A key derived from a passphrase, wiped on drop, with the parameters it was derived under
+#[derive(Clone)]
+pub struct DerivedKey {
+    kdf: KdfParams,
+    key: Zeroizing<[u8; KEY_LEN]>,
+}
+
+impl DerivedKey {
+    /// Derives a key under a fresh salt and the default parameters
+    pub fn generate(passphrase: &str) -> Result<Self, EncryptionError> {
+        let kdf = KdfParams {
+            algorithm: KDF.to_string(),
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+            salt: hex::encode(random_bytes::<SALT_LEN>()?),
+        };
+        Self::derive(passphrase, &kdf)
+    }
+
+    /// Derives the key `kdf` describes, e.g. one stored with a container
+    pub fn derive(passphrase: &str, kdf: &KdfParams) -> Result<Self, EncryptionError> {
+        if kdf.algorithm != KDF {
+            return Err(EncryptionError::Kdf(format!("unsupported algorithm {}", kdf.algorithm)));
+        }
+        let salt = hex::decode(&kdf.salt).map_err(|e| EncryptionError::Format(e.to_string()))?;
+        let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(KEY_LEN))
+            .map_err(|e| EncryptionError::Kdf(e.to_string()))?;
+
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut *key)
+            .map_err(|e| EncryptionError::Kdf(e.to_string()))?;
+        Ok(Self { kdf: kdf.clone(), key })
+    }
+
+    pub fn kdf(&self) -> &KdfParams {
+        &self.kdf
+    }
+
+    /// Seals `plaintext` under a fresh nonce
+    pub fn seal(&self, plaintext: &[u8]) -> Result<SealedData, EncryptionError> {
+        let nonce = random_bytes::<NONCE_LEN>()?;
+        let ciphertext = XChaCha20Poly1305::new(self.key.as_ref().into())
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: ASSOCIATED_DATA,
+                },
+            )
+            .map_err(|_| EncryptionError::Encrypt)?;
+
+        Ok(SealedData {
+            cipher: CIPHER.to_string(),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    /// Opens `sealed` to a buffer that is wiped when dropped
+    pub fn open(&self, sealed: &SealedData) -> Result<Zeroizing<Vec<u8>>, EncryptionError> {
+        if sealed.cipher != CIPHER {
+            return Err(EncryptionError::Format(format!("unsupported cipher {}", sealed.cipher)));
+        }
+        let nonce = hex::decode(&sealed.nonce).map_err(|e| EncryptionError::Format(e.to_string()))?;
+        if nonce.len() != NONCE_LEN {
+            return Err(EncryptionError::Format("nonce has the wrong length".to_string()));
+        }
+        let ciphertext =
+            hex::decode(&sealed.ciphertext).map_err(|e| EncryptionError::Format(e.to_string()))?;
+
+        XChaCha20Poly1305::new(self.key.as_ref().into())
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: ASSOCIATED_DATA,
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| EncryptionError::Decrypt)
+    }
+}
+
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<EncryptedContainer, EncryptionError> {
+    let key = DerivedKey::generate(passphrase)?;
+    let SealedData { cipher, nonce, ciphertext } = key.seal(plaintext)?;
+    Ok(EncryptedContainer {
+        cipher,
+        kdf: key.kdf,
+        nonce,
+        ciphertext,
+    })
+}
+
+/// Decrypts to a buffer that is wiped when dropped
+pub fn decrypt(
+    container: &EncryptedContainer,
+    passphrase: &str,
+) -> Result<Zeroizing<Vec<u8>>, EncryptionError> {
+    let sealed = SealedData {
+        cipher: container.cipher.clone(),
+        nonce: container.nonce.clone(),
+        ciphertext: container.ciphertext.clone(),
+    };
+    DerivedKey::derive(passphrase, &container.kdf)?.open(&sealed)
+}
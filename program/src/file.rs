@@ -0,0 +1,116 @@
+//! Commitments to files.
+//!
+//! A file is streamed through the same peppered hash as a typed input, in chunks, so a
+//! dataset or binary of any size can be committed without loading it into memory. Its
+//! bytes are hashed as they are, never normalized. The secret keeps only the pepper; the
+//! file itself is the input handed over at the reveal.
+
+use crate::seed::MasterSeed;
+use crate::{
+    pepper, CommitError, CommitHasher, CommitParams, Commitment, Normalization, PepperOptions, Secret,
+};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, ErrorKind, Read};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Bytes read from the file at a time
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// The committed file, recorded with the secret so it can be found and checked again
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileInput {
+    /// Absolute path the file was read from
+    pub path: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Hex-encoded unpeppered hash of the contents, with the commitment's algorithm. It
+    /// identifies the file, so it stays in the secrets file and is never published.
+    pub content_hash: String,
+}
+
+/// Streams the file at `path` once, giving its commitment under `params` with `pepper` and its details
+pub fn hash_file(params: &CommitParams, pepper: &[u8], path: &Path) -> io::Result<(Commitment, FileInput)> {
+    let mut commit_hasher = CommitHasher::new(params, pepper);
+    let mut content_hasher = params.algorithm.hasher();
+    let size = stream(path, |chunk| {
+        commit_hasher.update(chunk);
+        content_hasher.update(chunk);
+    })?;
+
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let input = FileInput {
+        path: path.display().to_string(),
+        size,
+        content_hash: hex::encode(content_hasher.finalize()),
+    };
+    Ok((commit_hasher.finalize(), input))
+}
+
+/// Feeds the file at `path` to `consume` chunk by chunk, returning its size
+pub(crate) fn stream(path: &Path, mut consume: impl FnMut(&[u8])) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut chunk = Zeroizing::new(vec![0u8; CHUNK_LEN]);
+    let mut size = 0u64;
+    loop {
+        let read = match file.read(&mut chunk) {
+            Ok(0) => return Ok(size),
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        consume(&chunk[..read]);
+        size += read as u64;
+    }
+}
+
+/// Creates a fresh pepper as `options` asks and commits to the file at `path` with it
+pub fn commit_file(
+    params: &CommitParams,
+    options: &PepperOptions,
+    path: &Path,
+) -> Result<(Commitment, Secret, FileInput), CommitError> {
+    params.check_pepper_len(options.len)?;
+    let (pepper, mix) = pepper::generate(options)?;
+    commit_with_secret(params, Secret::new(pepper, Vec::new()).with_mix(mix), path)
+}
+
+/// Like [`commit_file`] with the pepper `seed` derives for `counter` and `label`
+pub fn commit_file_derived(
+    params: &CommitParams,
+    seed: &MasterSeed,
+    counter: u64,
+    label: &str,
+    pepper_len: usize,
+    path: &Path,
+) -> Result<(Commitment, Secret, FileInput), CommitError> {
+    params.check_pepper_len(pepper_len)?;
+    let secret = Secret::new(seed.derive_pepper(counter, label, pepper_len), Vec::new())
+        .with_derivation(Some(seed.derivation(counter, label)));
+    commit_with_secret(params, secret, path)
+}
+
+fn commit_with_secret(
+    params: &CommitParams,
+    secret: Secret,
+    path: &Path,
+) -> Result<(Commitment, Secret, FileInput), CommitError> {
+    // The record should say how the bytes were really hashed
+    let params = CommitParams {
+        normalization: Normalization::Raw,
+        ..params.clone()
+    };
+    let (commitment, input) = hash_file(&params, secret.pepper(), path).map_err(CommitError::Input)?;
+    Ok((commitment, secret, input))
+}
+
+/// Checks that the file at `path` and `pepper` open `commitment`. A pepper length the
+/// commitment's scheme can't bind never matches.
+pub fn verify_file(commitment: &Commitment, pepper: &[u8], path: &Path) -> io::Result<bool> {
+    if commitment.params().check_pepper_len(pepper.len()).is_err() {
+        return Ok(false);
+    }
+    let (recomputed, _) = hash_file(commitment.params(), pepper, path)?;
+    Ok(recomputed == *commitment)
+}
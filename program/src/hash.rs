@@ -0,0 +1,243 @@
+//! Hash functions a commitment can be made with, and the ways pepper and input are fed to them.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use sha3::{Keccak256, Sha3_512};
+use std::fmt;
+use std::str::FromStr;
+use zeroize::Zeroizing;
+
+/// Hash function behind a commitment. The name is recorded with every saved secret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    #[default]
+    #[serde(rename = "sha512")]
+    Sha512,
+    #[serde(rename = "sha3-512")]
+    Sha3_512,
+    #[serde(rename = "blake3")]
+    Blake3,
+    #[serde(rename = "sha256")]
+    Sha256,
+    /// The original Keccak padding used by Ethereum contracts, not SHA3-256
+    #[serde(rename = "keccak256")]
+    Keccak256,
+}
+
+impl HashAlgorithm {
+    pub const ALL: [Self; 5] = [
+        Self::Sha512,
+        Self::Sha3_512,
+        Self::Blake3,
+        Self::Sha256,
+        Self::Keccak256,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Sha512 => "sha512",
+            Self::Sha3_512 => "sha3-512",
+            Self::Blake3 => "blake3",
+            Self::Sha256 => "sha256",
+            Self::Keccak256 => "keccak256",
+        }
+    }
+
+    /// The algorithm after this one in [`HashAlgorithm::ALL`], wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&algorithm| algorithm == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Digest size in bytes
+    pub const fn output_len(self) -> usize {
+        match self {
+            Self::Sha512 | Self::Sha3_512 => 64,
+            Self::Blake3 | Self::Sha256 | Self::Keccak256 => 32,
+        }
+    }
+
+    /// Input block size in bytes, which HMAC pads its key to
+    pub const fn block_size(self) -> usize {
+        match self {
+            Self::Sha512 => 128,
+            Self::Sha3_512 => 72,
+            Self::Blake3 => 64,
+            Self::Sha256 => 64,
+            Self::Keccak256 => 136,
+        }
+    }
+
+    pub fn hasher(self) -> Hasher {
+        match self {
+            Self::Sha512 => Hasher::Sha512(Sha512::new()),
+            Self::Sha3_512 => Hasher::Sha3_512(Sha3_512::new()),
+            Self::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            Self::Sha256 => Hasher::Sha256(Sha256::new()),
+            Self::Keccak256 => Hasher::Keccak256(Keccak256::new()),
+        }
+    }
+
+    /// Hashes the concatenation of `parts`
+    pub fn digest(self, parts: &[&[u8]]) -> Vec<u8> {
+        let mut hasher = self.hasher();
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize()
+    }
+
+    /// HMAC as in RFC 2104 over the concatenation of `parts`
+    pub fn hmac(self, key: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+        let mut hmac = self.hmac_hasher(key);
+        for part in parts {
+            hmac.update(part);
+        }
+        hmac.finalize()
+    }
+
+    /// Incremental form of [`HashAlgorithm::hmac`], for messages fed in pieces
+    pub fn hmac_hasher(self, key: &[u8]) -> HmacHasher {
+        const IPAD: u8 = 0x36;
+        const OPAD: u8 = 0x5c;
+
+        // Keys longer than a block are hashed first, then every key is zero-padded to a block
+        // The key is usually a pepper, so every copy of it is wiped
+        let mut block_key = Zeroizing::new(Vec::with_capacity(self.block_size()));
+        if key.len() > self.block_size() {
+            block_key.extend_from_slice(&Zeroizing::new(self.digest(&[key])));
+        } else {
+            block_key.extend_from_slice(key);
+        }
+        block_key.resize(self.block_size(), 0);
+        let padded = |pad: u8| Zeroizing::new(block_key.iter().map(|b| b ^ pad).collect::<Vec<u8>>());
+
+        let mut inner = self.hasher();
+        inner.update(&padded(IPAD));
+        HmacHasher {
+            algorithm: self,
+            inner,
+            outer_pad: padded(OPAD),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(|algorithm| algorithm.name()).collect();
+                format!("unknown hash algorithm {s:?}, expected one of {}", names.join(", "))
+            })
+    }
+}
+
+/// Incremental state of one of the [`HashAlgorithm`]s
+pub enum Hasher {
+    Sha512(Sha512),
+    Sha3_512(Sha3_512),
+    Blake3(Box<blake3::Hasher>),
+    Sha256(Sha256),
+    Keccak256(Keccak256),
+}
+
+impl Hasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha512(hasher) => hasher.update(data),
+            Self::Sha3_512(hasher) => hasher.update(data),
+            Self::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Self::Sha256(hasher) => hasher.update(data),
+            Self::Keccak256(hasher) => hasher.update(data),
+        }
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        match self {
+            Self::Sha512(hasher) => hasher.finalize().to_vec(),
+            Self::Sha3_512(hasher) => hasher.finalize().to_vec(),
+            Self::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+            Self::Sha256(hasher) => hasher.finalize().to_vec(),
+            Self::Keccak256(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
+
+/// Incremental HMAC state, holding the padded key until it is finalized
+pub struct HmacHasher {
+    algorithm: HashAlgorithm,
+    inner: Hasher,
+    outer_pad: Zeroizing<Vec<u8>>,
+}
+
+impl HmacHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        let inner = Zeroizing::new(self.inner.finalize());
+        let mut outer = self.algorithm.hasher();
+        outer.update(&self.outer_pad);
+        outer.update(&inner);
+        outer.finalize()
+    }
+}
+
+/// How the pepper and input are combined into a commitment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum CommitScheme {
+    /// `H(pepper || input)`, which every secret made before schemes existed uses
+    #[default]
+    #[serde(rename = "concat")]
+    Concat,
+    /// `HMAC-H(key = pepper, msg = input)`
+    #[serde(rename = "hmac")]
+    Hmac,
+}
+
+impl CommitScheme {
+    pub const ALL: [Self; 2] = [Self::Concat, Self::Hmac];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Concat => "concat",
+            Self::Hmac => "hmac",
+        }
+    }
+
+    /// The scheme after this one in [`CommitScheme::ALL`], wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&scheme| scheme == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for CommitScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for CommitScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|scheme| scheme.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown commitment scheme {s:?}, expected concat or hmac"))
+    }
+}
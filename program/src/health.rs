@@ -0,0 +1,167 @@
+//! Health tests on the bytes read from the OS RNG, after NIST SP 800-90B section 4.4.
+//!
+//! Both tests treat each byte as one sample and assume the full 8 bits of min-entropy
+//! the OS promises, with a false positive rate of 2^-20. They run over 1024 bytes before
+//! the first pepper is made and then over every byte that goes into a pepper. A failure
+//! is latched: a stuck RNG that recovers is not trusted again until the client restarts.
+
+use crate::{pepper, CommitError};
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+/// Identical bytes in a row that fail the repetition count test, 1 + ceil(20 / 8)
+pub const REPETITION_CUTOFF: usize = 4;
+/// Bytes in one adaptive proportion window
+pub const PROPORTION_WINDOW: usize = 512;
+/// Copies of a window's first byte within the window that fail the adaptive proportion test
+pub const PROPORTION_CUTOFF: usize = 13;
+/// Bytes tested before the first pepper is made
+pub const STARTUP_SAMPLES: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthFailure {
+    /// The same byte came out of the RNG too many times in a row
+    RepetitionCount,
+    /// One byte value came out far too often within a window
+    AdaptiveProportion,
+}
+
+impl fmt::Display for HealthFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RepetitionCount => write!(
+                f,
+                "repetition count test failed: the RNG returned the same byte {REPETITION_CUTOFF} times in a row"
+            ),
+            Self::AdaptiveProportion => write!(
+                f,
+                "adaptive proportion test failed: one byte value appeared {PROPORTION_CUTOFF} times in {PROPORTION_WINDOW} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HealthFailure {}
+
+/// State of both tests, carried over between peppers so a fault spanning two reads is caught
+struct Monitor {
+    started: bool,
+    failure: Option<HealthFailure>,
+    last: u8,
+    repeats: usize,
+    window_first: u8,
+    window_seen: usize,
+    window_matches: usize,
+}
+
+impl Monitor {
+    const fn new() -> Self {
+        Self {
+            started: false,
+            failure: None,
+            last: 0,
+            repeats: 0,
+            window_first: 0,
+            window_seen: 0,
+            window_matches: 0,
+        }
+    }
+
+    fn feed(&mut self, bytes: &[u8]) -> Result<(), HealthFailure> {
+        if let Some(failure) = self.failure {
+            return Err(failure);
+        }
+        for &byte in bytes {
+            if let Err(failure) = self.sample(byte) {
+                self.failure = Some(failure);
+                return Err(failure);
+            }
+        }
+        Ok(())
+    }
+
+    fn sample(&mut self, byte: u8) -> Result<(), HealthFailure> {
+        if self.repeats > 0 && byte == self.last {
+            self.repeats += 1;
+            if self.repeats >= REPETITION_CUTOFF {
+                return Err(HealthFailure::RepetitionCount);
+            }
+        } else {
+            self.last = byte;
+            self.repeats = 1;
+        }
+
+        if self.window_seen == 0 {
+            self.window_first = byte;
+            self.window_matches = 1;
+        } else if byte == self.window_first {
+            self.window_matches += 1;
+            if self.window_matches >= PROPORTION_CUTOFF {
+                return Err(HealthFailure::AdaptiveProportion);
+            }
+        }
+        self.window_seen = (self.window_seen + 1) % PROPORTION_WINDOW;
+        Ok(())
+    }
+}
+
+static MONITOR: Mutex<Monitor> = Mutex::new(Monitor::new());
+
+fn monitor() -> MutexGuard<'static, Monitor> {
+    MONITOR.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Runs the startup tests unless they already passed. Call early so a broken RNG is
+/// reported before the user types anything; peppers run it themselves otherwise.
+pub fn startup() -> Result<(), CommitError> {
+    if monitor().started {
+        return Ok(());
+    }
+    let samples = pepper::read_os_random(STARTUP_SAMPLES)?;
+    let mut monitor = monitor();
+    monitor.feed(&samples)?;
+    monitor.started = true;
+    Ok(())
+}
+
+/// Runs the continuous tests on bytes about to go into a pepper
+pub fn check(bytes: &[u8]) -> Result<(), HealthFailure> {
+    monitor().feed(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A window whose first byte, zero, appears `copies` times, never twice in a row
+    fn window(copies: usize) -> Vec<u8> {
+        let mut window: Vec<u8> = (0..PROPORTION_WINDOW).map(|i| (i % 255 + 1) as u8).collect();
+        for copy in 0..copies {
+            window[copy * 2] = 0;
+        }
+        window
+    }
+
+    #[test]
+    fn repetition_count_fails_on_the_fourth_repeat() {
+        let mut monitor = Monitor::new();
+        assert_eq!(monitor.feed(&[1, 7, 7, 7]), Ok(()));
+        assert_eq!(monitor.feed(&[7]), Err(HealthFailure::RepetitionCount));
+    }
+
+    #[test]
+    fn adaptive_proportion_fails_on_the_thirteenth_copy() {
+        assert_eq!(Monitor::new().feed(&window(PROPORTION_CUTOFF - 1)), Ok(()));
+        assert_eq!(
+            Monitor::new().feed(&window(PROPORTION_CUTOFF)),
+            Err(HealthFailure::AdaptiveProportion)
+        );
+    }
+
+    #[test]
+    fn failures_stay_latched() {
+        let mut monitor = Monitor::new();
+        assert!(monitor.feed(&[9; REPETITION_CUTOFF]).is_err());
+        assert_eq!(monitor.feed(&window(1)), Err(HealthFailure::RepetitionCount));
+    }
+}
@@ -0,0 +1,257 @@
+//! Append-only journal of secrets that have not been compacted into the secrets file.
+//!
+//! Each new secret is written as one JSON line and fsync'd before its hash is shown,
+//! so a published hash always has its reveal data on disk. Saving compacts the
+//! journal into the secrets file and removes it.
+//!
+//! With a passphrase, the first encrypted line is preceded by a header holding the KDF
+//! parameters, and every line after it is sealed with its own nonce under the key they
+//! derive. The key is kept for the rest of the session, so Argon2id runs once rather than
+//! once per line.
+
+use crate::encryption::{DerivedKey, EncryptedContainer, EncryptionError, KdfParams, SealedData};
+use crate::store::{self, SaveError, SecretRecord};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use std::io::{self, ErrorKind, Write};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use zeroize::Zeroizing;
+
+/// Sealed into every header, so a wrong passphrase is caught before a line is sealed under it
+const HEADER_CHECK: &[u8] = b"concoin-journal-v1";
+
+/// Key of the journal last read or extended and the passphrase it was derived from
+static SESSION_KEY: Mutex<Option<(Zeroizing<String>, DerivedKey)>> = Mutex::new(None);
+
+/// Leads the encrypted lines of a journal
+#[derive(Serialize, Deserialize)]
+struct Header {
+    journal_kdf: KdfParams,
+    /// [`HEADER_CHECK`] sealed under the key
+    check: SealedData,
+}
+
+/// The key that marks a header, probed without copying anything else on the line
+#[derive(Deserialize)]
+struct HeaderMarker {
+    journal_kdf: Option<IgnoredAny>,
+}
+
+impl Header {
+    fn detect(line: &str) -> bool {
+        line.trim_start().starts_with('{')
+            && serde_json::from_str::<HeaderMarker>(line).is_ok_and(|marker| marker.journal_kdf.is_some())
+    }
+
+    /// Starts a journal under a fresh salt
+    fn generate(passphrase: &str) -> Result<(Self, DerivedKey), EncryptionError> {
+        let key = DerivedKey::generate(passphrase)?;
+        let header = Self {
+            journal_kdf: key.kdf().clone(),
+            check: key.seal(HEADER_CHECK)?,
+        };
+        *session_key() = Some((Zeroizing::new(passphrase.to_string()), key.clone()));
+        Ok((header, key))
+    }
+
+    /// The key the following lines are sealed under, derived only if the session doesn't hold it yet
+    fn unlock(&self, passphrase: &str) -> Result<DerivedKey, EncryptionError> {
+        let mut cached = session_key();
+        let key = match &*cached {
+            Some((held, key)) if held.as_str() == passphrase && *key.kdf() == self.journal_kdf => key.clone(),
+            _ => DerivedKey::derive(passphrase, &self.journal_kdf)?,
+        };
+        key.open(&self.check)?;
+        *cached = Some((Zeroizing::new(passphrase.to_string()), key.clone()));
+        Ok(key)
+    }
+}
+
+/// An encrypted line. Lines written before journals had a header carry their own KDF parameters.
+#[derive(Deserialize)]
+struct SealedLine {
+    #[serde(default)]
+    kdf: Option<KdfParams>,
+    #[serde(flatten)]
+    sealed: SealedData,
+}
+
+fn session_key() -> MutexGuard<'static, Option<(Zeroizing<String>, DerivedKey)>> {
+    SESSION_KEY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn require(passphrase: Option<&str>) -> io::Result<&str> {
+    passphrase.ok_or_else(|| {
+        io::Error::new(ErrorKind::PermissionDenied, "Journal is encrypted and no passphrase was given")
+    })
+}
+
+/// The journal's contents, empty if there is none yet
+fn read(path: &Path) -> io::Result<Zeroizing<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(Zeroizing::new(contents)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Zeroizing::new(String::new())),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn journal_path(secrets_path: &str) -> String {
+    format!("{secrets_path}.journal")
+}
+
+/// Appends one secret and waits until it is on disk. With a passphrase the line is encrypted,
+/// after a header if the journal has none yet.
+pub fn append(secrets_path: &str, record: &SecretRecord, passphrase: Option<&str>) -> io::Result<()> {
+    let path = journal_path(secrets_path);
+    let path = Path::new(&path);
+    // Keep a concurrent compaction from removing the journal under this line, and a
+    // concurrent append from starting a second header
+    let _lock = store::lock(secrets_path).map_err(io::Error::other)?;
+
+    let mut line = Zeroizing::new(serde_json::to_string(record)?);
+    if let Some(passphrase) = passphrase {
+        let contents = read(path)?;
+        let header = contents.lines().rev().find(|line| Header::detect(line));
+        let (new_header, key) = match header {
+            Some(header) => {
+                let header: Header = serde_json::from_str(header)?;
+                (None, header.unlock(passphrase).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?)
+            }
+            None => {
+                let (header, key) = Header::generate(passphrase).map_err(io::Error::other)?;
+                (Some(header), key)
+            }
+        };
+        let sealed = key.seal(line.as_bytes()).map_err(io::Error::other)?;
+        line = Zeroizing::new(serde_json::to_string(&sealed)?);
+        if let Some(header) = new_header {
+            line.insert_str(0, &format!("{}\n", serde_json::to_string(&header)?));
+        }
+    }
+    line.push('\n');
+
+    let created = !path.exists();
+    let mut file = store::owner_only_options().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+    file.sync_all()?;
+    if created {
+        // Make the new directory entry durable too
+        store::sync_parent_dir(path)?;
+    }
+    Ok(())
+}
+
+/// Reads every journaled secret. A torn last line, left by a crash mid-append, is skipped:
+/// its append never finished, so its hash was never shown.
+pub fn load(path: impl AsRef<Path>, passphrase: Option<&str>) -> io::Result<Vec<SecretRecord>> {
+    let contents = read(path.as_ref())?;
+
+    let lines: Vec<&str> = contents.lines().filter(|line| !line.trim().is_empty()).collect();
+    let mut records = Vec::with_capacity(lines.len());
+    let mut key = None;
+    for (i, line) in lines.iter().enumerate() {
+        let torn = i + 1 == lines.len() && !contents.ends_with('\n');
+        // A torn line isn't valid JSON, so it is never taken for a header or a sealed line
+        if Header::detect(line) {
+            let header: Header = serde_json::from_str(line)?;
+            let unlocked = header.unlock(require(passphrase)?);
+            key = Some(unlocked.map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?);
+            continue;
+        }
+        let record = if EncryptedContainer::detect(line.as_bytes()) {
+            let passphrase = require(passphrase)?;
+            let line: SealedLine = serde_json::from_str(line)?;
+            let plaintext = match (&line.kdf, &key) {
+                (Some(kdf), _) => DerivedKey::derive(passphrase, kdf).and_then(|key| key.open(&line.sealed)),
+                (None, Some(key)) => key.open(&line.sealed),
+                (None, None) => {
+                    return Err(io::Error::new(ErrorKind::InvalidData, "Journal line is encrypted but has no header"));
+                }
+            };
+            serde_json::from_slice(&plaintext.map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?)?
+        } else {
+            match serde_json::from_str(line) {
+                Ok(record) => record,
+                Err(_) if torn => break,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Reads the secrets file plus any journaled secrets that were never compacted into it.
+/// A journaled reveal of a chain already in the file moves that chain forward.
+pub fn load_with_secrets(secrets_path: &str, passphrase: Option<&str>) -> io::Result<Vec<SecretRecord>> {
+    let mut secrets = store::load_data_from_json(secrets_path, passphrase)?;
+    for record in load(journal_path(secrets_path), passphrase)? {
+        match secrets.iter_mut().find(|held| held.commitment == record.commitment) {
+            Some(held) => held.advance_chain(&record),
+            None => secrets.push(record),
+        }
+    }
+    Ok(secrets)
+}
+
+/// Merges the journal and `data` into the secrets file, then removes the journal.
+/// Both are read with `passphrase` and the file is written with `new_passphrase`, so a
+/// save can also encrypt, re-key or decrypt the file. Returns the combined list that was written.
+pub fn compact(
+    data: &[SecretRecord],
+    secrets_path: &str,
+    passphrase: Option<&str>,
+    new_passphrase: Option<&str>,
+) -> Result<Vec<SecretRecord>, SaveError> {
+    let _lock = store::lock(secrets_path)?;
+    let path = journal_path(secrets_path);
+    let journaled = load(&path, passphrase).map_err(SaveError::Load)?;
+
+    let merged = store::merge_locked(journaled.iter().chain(data), secrets_path.as_ref(), passphrase, new_passphrase)?;
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(SaveError::RemoveJournal(e)),
+        _ => {}
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption;
+    use crate::store::SecretRecord;
+
+    fn record(input: &str) -> SecretRecord {
+        let (commitment, secret) = crate::commit(input).unwrap();
+        SecretRecord::new(&commitment, &secret, None)
+    }
+
+    #[test]
+    fn encrypted_lines_share_one_header() {
+        let secrets_path = std::env::temp_dir().join(format!("concoin-journal-{}.json", std::process::id()));
+        let secrets_path = secrets_path.to_str().unwrap();
+        let path = journal_path(secrets_path);
+        // A line from before journals had headers, sealed under its own key
+        let legacy = record("legacy");
+        let container = encryption::encrypt(serde_json::to_string(&legacy).unwrap().as_bytes(), "pw").unwrap();
+        std::fs::write(&path, format!("{}\n", serde_json::to_string(&container).unwrap())).unwrap();
+
+        let records = [record("a"), record("b")];
+        for record in &records {
+            append(secrets_path, record, Some("pw")).unwrap();
+        }
+        let wrong = append(secrets_path, &record("c"), Some("wrong"));
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let loaded = load(&path, Some("pw"));
+        let locked = load(&path, None);
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(format!("{secrets_path}.lock"));
+
+        assert_eq!(wrong.unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(contents.lines().filter(|line| Header::detect(line)).count(), 1);
+        assert_eq!(contents.lines().count(), 4);
+        assert_eq!(loaded.unwrap(), [legacy, records[0].clone(), records[1].clone()]);
+        assert_eq!(locked.unwrap_err().kind(), ErrorKind::PermissionDenied);
+    }
+}
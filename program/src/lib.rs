@@ -0,0 +1,362 @@
+//! Commitment primitives shared by the ConCoin client.
+//!
+//! A commitment is the hash of a random pepper followed by the input, SHA-512 unless
+//! another [`HashAlgorithm`] is chosen, or with [`CommitScheme::Hmac`] an HMAC of the
+//! input keyed by the pepper. An optional [`Context`] is hashed ahead of the pepper to
+//! bind the commitment to one round. Text inputs are NFC normalized first unless
+//! [`Normalization::Raw`] is chosen. The pepper and input together form the secret
+//! that is later revealed.
+
+use memory::SecretBytes;
+use std::fmt;
+use zeroize::Zeroizing;
+
+pub mod batch;
+pub mod chain;
+pub mod context;
+pub mod directory;
+pub mod encryption;
+pub mod file;
+pub mod hash;
+pub mod health;
+pub mod journal;
+pub mod kat;
+pub mod memory;
+pub mod merkle;
+pub mod normalize;
+pub mod pedersen;
+pub mod pepper;
+pub mod range;
+pub mod seed;
+pub mod store;
+
+pub use batch::BatchManifest;
+pub use chain::{ChainPosition, HashChain};
+pub use context::Context;
+pub use directory::TreeManifest;
+pub use file::FileInput;
+pub use hash::{CommitScheme, HashAlgorithm};
+pub use normalize::Normalization;
+pub use pedersen::{Opening, PedersenCommitment, PedersenInfo};
+pub use pepper::{PepperMix, PepperOptions};
+pub use range::CommitmentBundle;
+pub use seed::{Derivation, MasterSeed};
+
+/// Number of random bytes prepended to every input unless [`PepperOptions`] asks for another length
+pub const PEPPER_LEN: usize = 32;
+
+/// Reasons creating a commitment can fail
+#[derive(Debug)]
+pub enum CommitError {
+    /// The OS RNG could not provide a pepper
+    Rng(String),
+    /// The OS RNG output failed a health test, so no pepper is made from it
+    Health(health::HealthFailure),
+    /// The requested pepper length is outside the supported range
+    PepperLength(usize),
+    /// A pepper other than [`PEPPER_LEN`] bytes was asked for under the concat scheme
+    ConcatPepperLength(usize),
+    /// A stored secret is not valid hex
+    Hex(hex::FromHexError),
+    /// The stored secret does not reproduce the commitment that was just made
+    SelfCheck,
+    /// The secret could not be written to disk
+    Storage(std::io::Error),
+    /// The master seed for a derived pepper could not be loaded
+    Seed(seed::SeedError),
+    /// The file being committed to could not be read
+    Input(std::io::Error),
+    /// A batch was committed without any inputs
+    EmptyBatch,
+    /// The requested hash chain length is outside the supported range
+    ChainLength(u64),
+    /// A stored hash chain claims more revealed links than it has
+    ChainRevealed { length: u64, revealed: u64 },
+}
+
+impl fmt::Display for CommitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rng(e) => write!(f, "OS RNG failed to provide secure random bytes: {e}"),
+            Self::Health(e) => write!(f, "OS RNG is unhealthy, refusing to make a pepper: {e}"),
+            Self::PepperLength(len) => write!(
+                f,
+                "pepper length {len} is outside {}..={} bytes",
+                pepper::MIN_PEPPER_LEN,
+                pepper::MAX_PEPPER_LEN
+            ),
+            Self::ConcatPepperLength(len) => write!(
+                f,
+                "a {len}-byte pepper needs the hmac scheme, concat only takes {PEPPER_LEN}-byte peppers"
+            ),
+            Self::Hex(e) => write!(f, "hex decoding failed: {e}"),
+            Self::SelfCheck => f.write_str("stored secret does not reproduce the commitment"),
+            Self::Storage(e) => write!(f, "could not store the secret: {e}"),
+            Self::Seed(e) => write!(f, "could not derive the pepper: {e}"),
+            Self::Input(e) => write!(f, "could not read the input file: {e}"),
+            Self::EmptyBatch => f.write_str("a batch needs at least one input"),
+            Self::ChainLength(len) => write!(f, "chain length {len} is outside 1..={} links", chain::MAX_CHAIN_LEN),
+            Self::ChainRevealed { length, revealed } => {
+                write!(f, "a chain of {length} links can't have {revealed} of them revealed")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommitError {}
+
+impl From<std::io::Error> for CommitError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Storage(e)
+    }
+}
+
+impl From<health::HealthFailure> for CommitError {
+    fn from(e: health::HealthFailure) -> Self {
+        Self::Health(e)
+    }
+}
+
+impl From<seed::SeedError> for CommitError {
+    fn from(e: seed::SeedError) -> Self {
+        Self::Seed(e)
+    }
+}
+
+impl From<hex::FromHexError> for CommitError {
+    fn from(e: hex::FromHexError) -> Self {
+        Self::Hex(e)
+    }
+}
+
+/// Public choices that determine how a secret is turned into a commitment
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CommitParams {
+    pub algorithm: HashAlgorithm,
+    pub scheme: CommitScheme,
+    pub context: Option<Context>,
+    pub normalization: Normalization,
+}
+
+impl CommitParams {
+    /// Checks that a pepper of `len` bytes keeps commitments binding under these params.
+    /// The concat hash doesn't mark where the pepper ends, so a longer pepper could swallow
+    /// the start of the input; it only takes [`PEPPER_LEN`] bytes. HMAC keys with the pepper
+    /// apart from the input, so it takes any supported length.
+    pub fn check_pepper_len(&self, len: usize) -> Result<(), CommitError> {
+        if !(pepper::MIN_PEPPER_LEN..=pepper::MAX_PEPPER_LEN).contains(&len) {
+            return Err(CommitError::PepperLength(len));
+        }
+        if self.scheme == CommitScheme::Concat && len != PEPPER_LEN {
+            return Err(CommitError::ConcatPepperLength(len));
+        }
+        Ok(())
+    }
+}
+
+/// Hex-encoded hash that gets published before the reveal, and the parameters that made it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Commitment {
+    params: CommitParams,
+    hash: String,
+}
+
+impl Commitment {
+    /// Wraps an existing hex hash, e.g. one received from a counterparty
+    pub fn from_hex(params: CommitParams, hash: &str) -> Self {
+        Self {
+            params,
+            hash: hash.trim().to_ascii_lowercase(),
+        }
+    }
+
+    pub fn params(&self) -> &CommitParams {
+        &self.params
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.params.algorithm
+    }
+
+    pub fn as_hex(&self) -> &str {
+        &self.hash
+    }
+}
+
+impl fmt::Display for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.hash)
+    }
+}
+
+/// Pepper and input, the values needed to open a commitment. Both are wiped when dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Secret {
+    pepper: SecretBytes,
+    input: SecretBytes,
+    /// Entropy the pepper was extracted from, when the user supplied some
+    mix: Option<PepperMix>,
+    /// Seed, counter and label the pepper was derived from, when it wasn't random
+    derivation: Option<Derivation>,
+}
+
+impl Secret {
+    pub fn new(pepper: Vec<u8>, input: Vec<u8>) -> Self {
+        Self {
+            pepper: SecretBytes::new(pepper),
+            input: SecretBytes::new(input),
+            mix: None,
+            derivation: None,
+        }
+    }
+
+    pub fn with_mix(mut self, mix: Option<PepperMix>) -> Self {
+        self.mix = mix;
+        self
+    }
+
+    pub fn with_derivation(mut self, derivation: Option<Derivation>) -> Self {
+        self.derivation = derivation;
+        self
+    }
+
+    /// Parses the pepper hex followed by the input hex, as written to the secrets file
+    pub fn from_hex(combined: &str) -> Result<Self, hex::FromHexError> {
+        Self::from_hex_with_pepper_len(combined, PEPPER_LEN)
+    }
+
+    /// Like [`Secret::from_hex`] for a pepper of `pepper_len` bytes
+    pub fn from_hex_with_pepper_len(combined: &str, pepper_len: usize) -> Result<Self, hex::FromHexError> {
+        let mut pepper = hex::decode(combined.trim())?;
+        if pepper.len() < pepper_len {
+            return Err(hex::FromHexError::InvalidStringLength);
+        }
+        let input = pepper.split_off(pepper_len);
+        Ok(Self::new(pepper, input))
+    }
+
+    /// Pepper hex followed by input hex
+    pub fn to_hex(&self) -> Zeroizing<String> {
+        let mut combined_pepper_input = Zeroizing::new(String::with_capacity(2 * (self.pepper.len() + self.input.len())));
+        combined_pepper_input.push_str(&Zeroizing::new(hex::encode(&*self.pepper)));
+        combined_pepper_input.push_str(&Zeroizing::new(hex::encode(&*self.input)));
+        combined_pepper_input
+    }
+
+    pub fn pepper(&self) -> &[u8] {
+        &self.pepper
+    }
+
+    pub fn input(&self) -> &[u8] {
+        &self.input
+    }
+
+    pub fn mix(&self) -> Option<&PepperMix> {
+        self.mix.as_ref()
+    }
+
+    pub fn derivation(&self) -> Option<&Derivation> {
+        self.derivation.as_ref()
+    }
+
+    /// Recomputes the commitment this secret opens under `params`
+    pub fn commitment(&self, params: &CommitParams) -> Commitment {
+        let mut hasher = CommitHasher::new(params, &self.pepper);
+        hasher.update(&params.normalization.apply(&self.input));
+        hasher.finalize()
+    }
+}
+
+/// Commitment to an input fed in pieces, e.g. a file streamed from disk.
+/// The pieces are hashed as given, without [`Normalization`].
+pub struct CommitHasher {
+    params: CommitParams,
+    state: CommitState,
+}
+
+enum CommitState {
+    Concat(hash::Hasher),
+    Hmac(hash::HmacHasher),
+}
+
+impl CommitHasher {
+    pub fn new(params: &CommitParams, pepper: &[u8]) -> Self {
+        // Without a context nothing goes ahead of the input, matching the original construction
+        let context = params.context.as_ref().map(Context::encode).unwrap_or_default();
+        let state = match params.scheme {
+            CommitScheme::Concat => {
+                let mut hasher = params.algorithm.hasher();
+                hasher.update(&context);
+                hasher.update(pepper);
+                CommitState::Concat(hasher)
+            }
+            CommitScheme::Hmac => {
+                let mut hmac = params.algorithm.hmac_hasher(pepper);
+                hmac.update(&context);
+                CommitState::Hmac(hmac)
+            }
+        };
+        Self {
+            params: params.clone(),
+            state,
+        }
+    }
+
+    pub fn update(&mut self, input: &[u8]) {
+        match &mut self.state {
+            CommitState::Concat(hasher) => hasher.update(input),
+            CommitState::Hmac(hmac) => hmac.update(input),
+        }
+    }
+
+    pub fn finalize(self) -> Commitment {
+        let params = self.params.clone();
+        Commitment {
+            params,
+            hash: hex::encode(self.finalize_bytes()),
+        }
+    }
+
+    /// The raw digest, for commitments that are hashed further rather than published
+    pub fn finalize_bytes(self) -> Vec<u8> {
+        match self.state {
+            CommitState::Concat(hasher) => hasher.finalize(),
+            CommitState::Hmac(hmac) => hmac.finalize(),
+        }
+    }
+}
+
+/// Creates a fresh pepper and commits to `input` with it using SHA-512 and no context
+pub fn commit(input: impl AsRef<[u8]>) -> Result<(Commitment, Secret), CommitError> {
+    commit_with(&CommitParams::default(), input)
+}
+
+/// Creates a fresh pepper and commits to `input` with it under `params`
+pub fn commit_with(
+    params: &CommitParams,
+    input: impl AsRef<[u8]>,
+) -> Result<(Commitment, Secret), CommitError> {
+    commit_with_options(params, &PepperOptions::default(), input)
+}
+
+/// Like [`commit_with`], making the pepper as `options` asks
+pub fn commit_with_options(
+    params: &CommitParams,
+    options: &PepperOptions,
+    input: impl AsRef<[u8]>,
+) -> Result<(Commitment, Secret), CommitError> {
+    params.check_pepper_len(options.len)?;
+    let (pepper, mix) = pepper::generate(options)?;
+    // The secret holds the bytes that get hashed, so they are also the ones stored and revealed
+    let input = params.normalization.apply(input.as_ref());
+    let secret = Secret::new(pepper, input.to_vec()).with_mix(mix);
+    Ok((secret.commitment(params), secret))
+}
+
+/// Checks that `secret` opens `commitment` under the parameters the commitment carries,
+/// including its context, so a reveal for another round fails. A pepper length the
+/// commitment's scheme can't bind never matches.
+pub fn verify(commitment: &Commitment, secret: &Secret) -> bool {
+    commitment.params().check_pepper_len(secret.pepper().len()).is_ok()
+        && secret.commitment(commitment.params()) == *commitment
+}
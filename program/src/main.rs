@@ -1,41 +1,152 @@
-use rand::rngs::OsRng;
-use rand::rand_core::TryRngCore;
+mod cli;
+mod recovery;
 
-use sha2::{Digest, Sha512};
-use std::io::{self};
-use hex;
+use clap::Parser;
+use program::batch;
+use program::chain::{ChainPosition, HashChain};
+use program::file;
+use program::seed::{self, MasterSeed};
+use program::{health, journal, kat, memory};
+use program::store::{self, SecretRecord};
+use program::{
+    commit_with_options, verify, CommitError, CommitParams, CommitScheme, Commitment, Normalization, PepperOptions,
+    Secret,
+};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+use zeroize::{Zeroize, Zeroizing};
 
 use color_eyre::Result;
 use ratatui::{
-    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
     layout::{Constraint, Flex, Layout, Position},
     style::{Color, Style, Stylize},
     text::{Line, Span, Text},
     prelude::{Rect},
-    widgets::{Block, Clear, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph, Tabs, Wrap},
     DefaultTerminal, Frame,
 };
 
-fn main() -> Result<()> {
+fn main() -> Result<ExitCode> {
     color_eyre::install()?;
+    let cli = cli::Cli::parse();
+    let passphrase = cli.passphrase.map(Zeroizing::new);
+    let passphrase = passphrase.as_deref().map(String::as_str);
+    if let Some(command) = cli.command {
+        let pepper_options = cli.params.pepper_options();
+        return cli::run(command, &cli.file, passphrase, &cli.params.params(), pepper_options);
+    }
+
+    // Before the terminal is taken over, so a failure here leaves it usable
+    recovery::install(&cli.file)?;
     let terminal = ratatui::init();
-    let app_result = App::new().run(terminal);
+    let pepper_options = cli.params.pepper_options();
+    let mut app = App::new(cli.file, passphrase, cli.params.params(), pepper_options);
+    app.masked = cli.masked;
+    let app_result = app.run(terminal);
     ratatui::restore();
-    app_result
+    app_result.map(|()| ExitCode::SUCCESS)
+}
+
+/// Short description of the scheme, algorithm, normalization and context, e.g. for list entries and titles
+fn describe_params(params: &CommitParams) -> String {
+    let mut construction = match params.scheme {
+        CommitScheme::Concat => params.algorithm.to_string(),
+        scheme => format!("{scheme}-{}", params.algorithm),
+    };
+    if params.normalization == Normalization::Raw {
+        construction.push_str(" raw");
+    }
+    match &params.context {
+        Some(context) => format!("{construction} [{context}]"),
+        None => construction,
+    }
+}
+
+/// Short description of a committed file, directory, batch, hash chain or Pedersen sum for list entries,
+/// None for typed input
+fn describe_input(record: &SecretRecord) -> Option<String> {
+    if let Some(info) = record.pedersen.as_ref().filter(|info| !info.parts.is_empty()) {
+        return Some(format!("sum of {} commitments", info.parts.len()));
+    }
+    if let Some(position) = &record.chain {
+        return Some(format!("hash chain, {} of {} links revealed", position.revealed, position.length));
+    }
+    if let Some(file_input) = &record.file {
+        Some(format!("file {} ({} bytes)", file_input.path, file_input.size))
+    } else if let Some(tree) = &record.tree {
+        Some(format!("directory {} ({} files)", tree.root_dir, tree.files.len()))
+    } else {
+        record.batch.as_ref().map(|batch| format!("batch of {} inputs", batch.inputs.len()))
+    }
 }
 
 /// App holds the state of the application
 struct App {
-    /// Current value of the input box
-    input: String,
+    /// Current value of the input box, wiped once committed
+    input: Zeroizing<String>,
     /// Position of cursor in the editor area.
     character_index: usize,
     /// Current input mode
     input_mode: InputMode,
+    /// Whether the input and plaintext previews are drawn as bullets
+    masked: bool,
+    /// Shows masked text until the next key press
+    peek: bool,
     /// History of recorded hashes
     hash: Vec<String>,
+    /// Algorithm and context new commitments are made with and pasted hashes are checked with
+    params: CommitParams,
+    /// Pepper length and user entropy new commitments are made with
+    pepper_options: PepperOptions,
+    /// Whether new peppers are derived from the master seed instead of drawn at random
+    derive: bool,
+    /// Inputs collected for one batch commitment while batch mode is on
+    batch: Option<Vec<Zeroizing<String>>>,
     // Stores secret input and pepper
-    secrets: Vec<Vec<String>>, 
+    secrets: Vec<SecretRecord>,
+    /// File the secrets are saved to
+    secrets_path: String,
+
+    /// Screen currently shown below the help line
+    screen: Screen,
+    /// Commitment hash pasted into the Verify screen
+    verify_hash: String,
+    /// Revealed pepper+input hex pasted into the Verify screen
+    verify_secret: String,
+    /// Field of the Verify screen that receives typed characters
+    verify_field: VerifyField,
+    /// File the Verify screen checks, in which case the secret field holds only the pepper
+    verify_file: Option<PathBuf>,
+    /// Last check of `verify_file`, kept so the file isn't rehashed on every redraw
+    file_verdict: Option<FileVerdict>,
+
+    /// Open file picker, which takes all key presses while shown
+    file_picker: Option<FilePicker>,
+
+    /// Open passphrase popup, which takes all key presses while shown
+    passphrase_prompt: Option<PassphrasePrompt>,
+    /// Passphrase the secrets file was last loaded or saved with
+    passphrase: Option<Zeroizing<String>>,
+    /// Set when loading the secrets file failed or was skipped. Nothing is written until it is
+    /// read, since new secrets would otherwise be journaled unencrypted or under the wrong
+    /// passphrase next to it.
+    locked: bool,
+
+    /// Set while asking whether to save an encrypted file unencrypted
+    decrypt_prompt: bool,
+
+    /// Mnemonic of a just created master seed, shown once until x is pressed
+    mnemonic_popup: Option<Zeroizing<String>>,
+
+    /// Open popup collecting user entropy, which takes all key presses while shown
+    entropy_prompt: Option<EntropyPrompt>,
+
+    /// Secrets found in a recovery file, waiting for the user to import or ignore them
+    recovery_prompt: Option<Vec<SecretRecord>>,
+    /// Whether the recovery file was imported and can be removed after the next save
+    recovery_imported: bool,
 
     show_saved_popup: bool,
     //Info shows detail, result returns Success or Failure
@@ -43,26 +154,372 @@ struct App {
     saved_popup_result: String,
 }
 
+#[derive(PartialEq)]
 enum InputMode {
     Normal,
     Editing,
 }
 
-impl App {
-    const fn new() -> Self {
+#[derive(Clone, Copy, PartialEq)]
+enum Screen {
+    Commit,
+    Verify,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VerifyField {
+    Hash,
+    Secret,
+}
+
+/// What the passphrase popup unlocks once Enter is pressed
+#[derive(Clone, Copy, PartialEq)]
+enum PassphrasePurpose {
+    Load,
+    Save,
+}
+
+/// Outcome of checking the chosen file, with the values it was checked against
+struct FileVerdict {
+    hash: String,
+    pepper: String,
+    params: CommitParams,
+    result: Result<bool, String>,
+}
+
+/// Lists a directory so a file can be chosen to commit to or verify
+struct FilePicker {
+    dir: PathBuf,
+    /// Names in `dir` and whether each is a directory, directories first
+    entries: Vec<(String, bool)>,
+    selected: usize,
+    /// Why the directory could not be listed
+    error: Option<String>,
+}
+
+impl FilePicker {
+    fn new(dir: PathBuf) -> Self {
+        let mut picker = Self {
+            dir,
+            entries: Vec::new(),
+            selected: 0,
+            error: None,
+        };
+        picker.list();
+        picker
+    }
+
+    fn list(&mut self) {
+        self.selected = 0;
+        self.entries.clear();
+        self.error = None;
+        if self.dir.parent().is_some() {
+            self.entries.push(("..".to_string(), true));
+        }
+        match std::fs::read_dir(&self.dir) {
+            Ok(read) => {
+                let mut entries: Vec<(String, bool)> = read
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| (entry.file_name().to_string_lossy().into_owned(), entry.path().is_dir()))
+                    .collect();
+                entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                self.entries.extend(entries);
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(self.entries.len().saturating_sub(1));
+    }
+
+    fn parent(&mut self) {
+        if let Some(parent) = self.dir.parent() {
+            self.dir = parent.to_path_buf();
+            self.list();
+        }
+    }
+
+    /// Opens the selected directory, or returns the selected file
+    fn choose(&mut self) -> Option<PathBuf> {
+        let (name, is_dir) = self.entries.get(self.selected)?.clone();
+        if !is_dir {
+            return Some(self.dir.join(name));
+        }
+        if name == ".." {
+            self.parent();
+        } else {
+            self.dir.push(name);
+            self.list();
+        }
+        None
+    }
+}
+
+struct PassphrasePrompt {
+    purpose: PassphrasePurpose,
+    input: Zeroizing<String>,
+    /// Why the previous attempt failed
+    error: Option<String>,
+}
+
+/// Collects typed characters, e.g. dice rolls, and the timing between key presses
+struct EntropyPrompt {
+    typed: Zeroizing<String>,
+    /// Nanoseconds between consecutive key presses, big-endian
+    timings: Zeroizing<Vec<u8>>,
+    last_key: Instant,
+}
+
+impl EntropyPrompt {
+    fn new() -> Self {
+        Self {
+            typed: Zeroizing::new(String::new()),
+            timings: Zeroizing::new(Vec::new()),
+            last_key: Instant::now(),
+        }
+    }
+
+    fn record(&mut self, typed: char) {
+        let now = Instant::now();
+        #[allow(clippy::cast_possible_truncation)]
+        let elapsed = now.duration_since(self.last_key).as_nanos() as u64;
+        if self.timings.len() == self.timings.capacity() {
+            // Grow by hand so the old buffer is wiped rather than freed as is
+            let mut grown = Zeroizing::new(Vec::with_capacity((self.timings.capacity() * 2).max(256)));
+            grown.extend_from_slice(&self.timings);
+            self.timings = grown;
+        }
+        self.timings.extend_from_slice(&elapsed.to_be_bytes());
+        self.last_key = now;
+        memory::reserve_wiping(&mut self.typed, typed.len_utf8());
+        self.typed.push(typed);
+    }
+
+    /// The typed characters followed by the timings, or None if nothing was typed
+    fn entropy(self) -> Option<Zeroizing<Vec<u8>>> {
+        if self.typed.is_empty() {
+            return None;
+        }
+        let mut entropy = Zeroizing::new(Vec::with_capacity(self.typed.len() + self.timings.len()));
+        entropy.extend_from_slice(self.typed.as_bytes());
+        entropy.extend_from_slice(&self.timings);
+        Some(entropy)
+    }
+}
+
+impl PassphrasePrompt {
+    fn new(purpose: PassphrasePurpose) -> Self {
         Self {
-            input: String::new(),
+            purpose,
+            input: Zeroizing::new(String::new()),
+            error: None,
+        }
+    }
+}
+
+impl App {
+    fn new(
+        secrets_path: String,
+        passphrase: Option<&str>,
+        params: CommitParams,
+        pepper_options: PepperOptions,
+    ) -> Self {
+        let mut app = Self {
+            input: Zeroizing::new(String::new()),
             input_mode: InputMode::Normal,
+            masked: false,
+            peek: false,
             hash: Vec::new(),
+            params,
+            pepper_options,
+            derive: false,
+            batch: None,
             secrets: Vec::new(),
+            secrets_path,
             character_index: 0,
 
+            screen: Screen::Commit,
+            verify_hash: String::new(),
+            verify_secret: String::new(),
+            verify_field: VerifyField::Hash,
+            verify_file: None,
+            file_verdict: None,
+
+            file_picker: None,
+
+            passphrase_prompt: None,
+            passphrase: None,
+            locked: false,
+
+            decrypt_prompt: false,
+
+            mnemonic_popup: None,
+
+            entropy_prompt: None,
+
+            recovery_prompt: None,
+            recovery_imported: false,
+
             show_saved_popup: false,
             saved_popup_info: String::new(),
             saved_popup_result: String::new(),
+        };
+        match store::is_encrypted(&app.secrets_path) {
+            Ok(true) if passphrase.is_none() => {
+                app.passphrase_prompt = Some(PassphrasePrompt::new(PassphrasePurpose::Load));
+            }
+            _ => match app.read_secrets(passphrase) {
+                Ok(secrets) => {
+                    app.restore_secrets(secrets);
+                    app.set_passphrase(passphrase.map(|passphrase| Zeroizing::new(passphrase.to_string())));
+                    app.check_recovery();
+                }
+                Err(e) => {
+                    // Journaling under a passphrase that can't read the file would make it unreadable
+                    let mut retry = PassphrasePrompt::new(PassphrasePurpose::Load);
+                    retry.error = Some(e.to_string());
+                    app.passphrase_prompt = Some(retry);
+                    app.locked = true;
+                }
+            },
+        }
+        // Shown over any loading error, since it means new commitments can't be trusted
+        if let Err(e) = kat::run() {
+            app.show_popup("Failure", format!("Self-test failed, commitments may not verify elsewhere: {e}. Press x to close"));
+        } else if let Err(e) = health::startup() {
+            app.show_popup("Failure", format!("{e}. New commitments are disabled. Press x to close"));
+        }
+        app
+    }
+
+    fn set_passphrase(&mut self, passphrase: Option<Zeroizing<String>>) {
+        recovery::set_passphrase(passphrase.clone());
+        self.passphrase = passphrase;
+    }
+
+    fn passphrase(&self) -> Option<&str> {
+        self.passphrase.as_deref().map(String::as_str)
+    }
+
+    /// Offers to import secrets an earlier session saved in an emergency. A recovery file
+    /// holding nothing that isn't already loaded, e.g. from the journal, is removed instead.
+    fn check_recovery(&mut self) {
+        match recovery::load(&self.secrets_path, self.passphrase()) {
+            Ok(recovered) if recovered.is_empty() => {}
+            Ok(recovered) => {
+                let recovered: Vec<_> = recovered.into_iter().filter(|record| self.adds_to_held(record)).collect();
+                if !recovered.is_empty() {
+                    self.recovery_prompt = Some(recovered);
+                } else if let Err(e) = recovery::remove(&self.secrets_path) {
+                    self.show_recovery_removal_error(e);
+                }
+            }
+            Err(e) => {
+                let info = format!(
+                    "Error reading {:?}: {}. Press x to close",
+                    recovery::recovery_path(&self.secrets_path),
+                    e
+                );
+                self.show_popup("Failure", info);
+            }
         }
     }
 
+    /// Whether `record` is missing from the held secrets, or reveals more of a held chain
+    fn adds_to_held(&self, record: &SecretRecord) -> bool {
+        match self.secrets.iter().find(|held| held.commitment == record.commitment) {
+            Some(held) => matches!((held.chain, record.chain), (Some(held), Some(other)) if other.revealed > held.revealed),
+            None => true,
+        }
+    }
+
+    fn show_recovery_removal_error(&mut self, e: std::io::Error) {
+        let info = format!(
+            "Error removing {:?}: {}. Press x to close",
+            recovery::recovery_path(&self.secrets_path),
+            e
+        );
+        self.show_popup("Failure", info);
+    }
+
+    /// Adds the recovered secrets that aren't already held. They count as unsaved until `s` is pressed.
+    fn import_recovery(&mut self) {
+        let Some(recovered) = self.recovery_prompt.take() else {
+            return;
+        };
+        let mut imported = 0;
+        for record in recovered {
+            match self.secrets.iter_mut().find(|held| held.commitment == record.commitment) {
+                Some(held) => held.advance_chain(&record),
+                None => {
+                    recovery::track(record.clone());
+                    self.secrets.push(record);
+                    imported += 1;
+                }
+            }
+        }
+        let secrets = std::mem::take(&mut self.secrets);
+        self.restore_secrets(secrets);
+        self.recovery_imported = true;
+        self.show_popup("Success", format!("Imported {imported} recovered secrets. Press s to save them, x to close."));
+    }
+
+    /// Reads the secrets file plus any journaled secrets that were never compacted into it
+    fn read_secrets(&self, passphrase: Option<&str>) -> std::io::Result<Vec<SecretRecord>> {
+        journal::load_with_secrets(&self.secrets_path, passphrase)
+    }
+
+    /// Replaces the held secrets and recomputes the Hash list from them
+    fn restore_secrets(&mut self, secrets: Vec<SecretRecord>) {
+        self.hash = secrets
+            .iter()
+            .map(|record| {
+                let described = describe_input(record).map(|input| format!(" {input}")).unwrap_or_default();
+                // Pedersen commitments are opened on the curve, not rehashed
+                if record.pedersen.is_some() {
+                    return match record.pedersen_opening() {
+                        Ok(Some(opening)) if opening.commitment().to_string() == record.commitment => {
+                            format!("pedersen {}{described}", record.commitment)
+                        }
+                        Ok(_) => format!("pedersen {}{described} (opening does not match)", record.commitment),
+                        Err(e) => format!("Unreadable secret: {e}"),
+                    };
+                }
+                match (record.secret(), &record.file) {
+                    // Files aren't rehashed here, so loading stays fast however large they are
+                    (Ok(_), Some(_)) => {
+                        format!("{} {}{described}", describe_params(&record.params()), record.commitment)
+                    }
+                    (Ok(secret), None) => {
+                        let params = record.params();
+                        let recomputed = match record.chain {
+                            // Chains are listed under their stored tip, which can take a million hashes to recompute
+                            Some(position) => match position.check() {
+                                Ok(()) => record.commitment(),
+                                Err(e) => return format!("Unreadable secret: {e}"),
+                            },
+                            None => secret.commitment(&params),
+                        };
+                        if recomputed != record.commitment() {
+                            format!("{} {recomputed}{described} (does not match stored hash {})", describe_params(&params), record.commitment)
+                        } else if !record.mix_matches() {
+                            format!("{} {recomputed}{described} (pepper does not match its recorded entropy)", describe_params(&params))
+                        } else {
+                            format!("{} {recomputed}{described}", describe_params(&params))
+                        }
+                    }
+                    (Err(e), _) => format!("Unreadable secret: {e}"),
+                }
+            })
+            .collect();
+        self.secrets = secrets;
+    }
+
     fn move_cursor_left(&mut self) {
         let cursor_moved_left = self.character_index.saturating_sub(1);
         self.character_index = self.clamp_cursor(cursor_moved_left);
@@ -73,9 +530,27 @@ impl App {
         self.character_index = self.clamp_cursor(cursor_moved_right);
     }
 
+    /// Text field that typed characters currently go to
+    fn active_input(&self) -> &String {
+        match (self.screen, self.verify_field) {
+            (Screen::Commit, _) => &self.input,
+            (Screen::Verify, VerifyField::Hash) => &self.verify_hash,
+            (Screen::Verify, VerifyField::Secret) => &self.verify_secret,
+        }
+    }
+
+    fn active_input_mut(&mut self) -> &mut String {
+        match (self.screen, self.verify_field) {
+            (Screen::Commit, _) => &mut self.input,
+            (Screen::Verify, VerifyField::Hash) => &mut self.verify_hash,
+            (Screen::Verify, VerifyField::Secret) => &mut self.verify_secret,
+        }
+    }
+
     fn enter_char(&mut self, new_char: char) {
         let index = self.byte_index();
-        self.input.insert(index, new_char);
+        memory::reserve_wiping(self.active_input_mut(), new_char.len_utf8());
+        self.active_input_mut().insert(index, new_char);
         self.move_cursor_right();
     }
 
@@ -84,108 +559,379 @@ impl App {
     /// Since each character in a string can be contain multiple bytes, it's necessary to calculate
     /// the byte index based on the index of the character.
     fn byte_index(&self) -> usize {
-        self.input
+        let input = self.active_input();
+        input
             .char_indices()
             .map(|(i, _)| i)
             .nth(self.character_index)
-            .unwrap_or(self.input.len())
+            .unwrap_or(input.len())
     }
 
     fn delete_char(&mut self) {
         let is_not_cursor_leftmost = self.character_index != 0;
         if is_not_cursor_leftmost {
-            // Method "remove" is not used on the saved text for deleting the selected char.
-            // Reason: Using remove on String works on bytes instead of the chars.
-            // Using remove would require special care because of char boundaries.
-
-            let current_index = self.character_index;
-            let from_left_to_current_index = current_index - 1;
-
-            // Getting all characters before the selected character.
-            let before_char_to_delete = self.input.chars().take(from_left_to_current_index);
-            // Getting all characters after selected character.
-            let after_char_to_delete = self.input.chars().skip(current_index);
-
-            // Put all characters together except the selected one.
-            // By leaving the selected one out, it is forgotten and therefore deleted.
-            self.input = before_char_to_delete.chain(after_char_to_delete).collect();
+            // Edited in place, so the old text isn't left behind in a dropped allocation
             self.move_cursor_left();
+            let index = self.byte_index();
+            memory::remove_wiping(self.active_input_mut(), index);
         }
     }
 
     fn clamp_cursor(&self, new_cursor_pos: usize) -> usize {
-        new_cursor_pos.clamp(0, self.input.chars().count())
+        new_cursor_pos.clamp(0, self.active_input().chars().count())
     }
 
     fn reset_cursor(&mut self) {
         self.character_index = 0;
     }
 
+    /// Puts the cursor after the last character of the active field
+    fn cursor_to_end(&mut self) {
+        self.character_index = self.active_input().chars().count();
+    }
+
+    fn switch_screen(&mut self) {
+        self.screen = match self.screen {
+            Screen::Commit => Screen::Verify,
+            Screen::Verify => Screen::Commit,
+        };
+        self.cursor_to_end();
+    }
+
+    fn switch_verify_field(&mut self) {
+        self.verify_field = match self.verify_field {
+            VerifyField::Hash => VerifyField::Secret,
+            VerifyField::Secret => VerifyField::Hash,
+        };
+        self.cursor_to_end();
+    }
+
+    /// Checks the pasted secret against the pasted hash.
+    /// Returns None until both fields have been filled in.
+    fn verify_result(&self) -> Option<Result<(bool, String), String>> {
+        if self.verify_hash.is_empty() || self.verify_secret.is_empty() {
+            return None;
+        }
+        // The split between pepper and input is only binding at lengths the scheme allows
+        if let Err(e) = self.params.check_pepper_len(self.pepper_options.len) {
+            return Some(Err(format!("Can't verify: {e}")));
+        }
+        let secret = match Secret::from_hex_with_pepper_len(&self.verify_secret, self.pepper_options.len) {
+            Ok(secret) => secret,
+            Err(e) => return Some(Err(format!("Secret is not valid pepper+input hex: {e}"))),
+        };
+        let matches = verify(&Commitment::from_hex(self.params.clone(), &self.verify_hash), &secret);
+        // The plaintext input is everything after the pepper
+        let input = String::from_utf8_lossy(secret.input()).into_owned();
+        Some(Ok((matches, input)))
+    }
+
+    /// Shows the popup with a Success or Failure title until x is pressed
+    fn show_popup(&mut self, result: &str, info: String) {
+        self.saved_popup_info = info;
+        self.saved_popup_result = result.to_string();
+        self.show_saved_popup = true;
+    }
+
     fn submit_input(&mut self) {
-        fn generate_secure_pepper() -> Vec<u8> {
-            let mut os_rng = OsRng;
-    
-            let mut pepper_bytes = [0u8; 32];
-    
-            os_rng.try_fill_bytes(&mut pepper_bytes)
-                .expect("Fatal Error: OS RNG failed to provide secure random bytes.");
+        // In batch mode the input waits to be committed with the rest of the batch
+        if let Some(batch) = &mut self.batch {
+            batch.push(std::mem::replace(&mut self.input, Zeroizing::new(String::new())));
+            self.reset_cursor();
+            return;
+        }
+        match self.commit_input() {
+            Ok(()) => {
+                self.input.zeroize();
+                self.reset_cursor();
+            }
+            // Keep the input so the commit can be retried
+            Err(e) => self.show_popup("Failure", format!("Commit failed: {e}. Press x to close")),
+        }
+    }
+
+    fn commit_input(&mut self) -> Result<(), CommitError> {
+        let (commitment, secret) = if self.derive {
+            let (master_seed, counter) = seed::next_counter(&self.secrets_path, self.passphrase())?;
+            seed::commit_derived(&self.params, &master_seed, counter, "", self.pepper_options.len, &self.input)?
+        } else {
+            commit_with_options(&self.params, &self.pepper_options, &self.input)?
+        };
+        self.store_commitment(SecretRecord::new(&commitment, &secret, None))
+    }
 
-            return pepper_bytes.to_vec();
+    /// Starts collecting inputs for a batch, or commits the collected ones under one Merkle root
+    fn toggle_batch(&mut self) {
+        match self.batch.take() {
+            None => self.batch = Some(Vec::new()),
+            Some(inputs) if inputs.is_empty() => {}
+            Some(inputs) => {
+                if let Err(e) = self.commit_batch(&inputs) {
+                    self.show_popup("Failure", format!("Batch commit failed: {e}. Press x to close"));
+                    // Keep the inputs so the commit can be retried
+                    self.batch = Some(inputs);
+                }
+            }
         }
+    }
 
-        fn hash_input_with_pepper(input: &str, pepper: &[u8]) -> String {
-            let mut hasher = Sha512::new();
+    fn commit_batch(&mut self, inputs: &[Zeroizing<String>]) -> Result<(), CommitError> {
+        let inputs: Vec<&str> = inputs.iter().map(|input| input.as_str()).collect();
+        let (commitment, secret, manifest) = batch::commit_batch(&self.params, &self.pepper_options, &inputs)?;
+        self.store_commitment(SecretRecord::new(&commitment, &secret, None).with_batch(Some(manifest)))
+    }
 
-            hasher.update(pepper);        
-            hasher.update(input.as_bytes());  
+    /// Starts a hash chain with as many links as the typed number
+    fn start_chain(&mut self) {
+        let result = match self.input.trim().parse::<u64>() {
+            Ok(length) => HashChain::generate(&self.pepper_options, length)
+                .and_then(|chain| self.store_commitment(SecretRecord::from_chain(&chain, None))),
+            Err(_) => {
+                self.show_popup("Failure", "Type the number of links first, then press c. Press x to close".into());
+                return;
+            }
+        };
+        match result {
+            Ok(()) => {
+                self.input.zeroize();
+                self.reset_cursor();
+            }
+            Err(e) => self.show_popup("Failure", format!("Chain creation failed: {e}. Press x to close")),
+        }
+    }
 
-            let result = hex::encode(hasher.finalize());
+    /// Reveals the next link of the latest hash chain that has links left
+    fn reveal_link(&mut self) {
+        let revealed = self.secrets.iter().enumerate().rev().find_map(|(index, record)| {
+            let mut chain = record.hash_chain().ok().flatten()?;
+            let link = chain.reveal_next()?;
+            Some((index, chain.position(), link))
+        });
+        let Some((index, position, link)) = revealed else {
+            self.show_popup("Failure", "No hash chain has links left to reveal. Press x to close".into());
+            return;
+        };
+        // Only show the link once its new position is on disk, so no link is handed out twice
+        match self.store_chain_position(index, position) {
+            Ok(()) => self.show_popup(
+                "Success",
+                format!("Link {} of {}: {}. Press x to close", position.revealed, position.length, hex::encode(&*link)),
+            ),
+            Err(e) => self.show_popup("Failure", format!("Reveal failed: {e}. Press x to close")),
+        }
+    }
+
+    /// Journals the chain held at `index` at its new position and lists it there
+    fn store_chain_position(&mut self, index: usize, position: ChainPosition) -> Result<(), CommitError> {
+        self.ensure_unlocked()?;
+        let mut record = self.secrets[index].clone();
+        record.chain = Some(position);
+        journal::append(&self.secrets_path, &record, self.passphrase())?;
+        self.hash[index] = format!(
+            "{} {} {}",
+            describe_params(&record.params()),
+            record.commitment,
+            describe_input(&record).unwrap_or_default()
+        );
+        recovery::track(record.clone());
+        self.secrets[index] = record;
+        Ok(())
+    }
 
-            // Return the final hex-encoded hash string
-            return result
+    fn submit_file(&mut self, path: &Path) {
+        if let Err(e) = self.commit_file(path) {
+            self.show_popup("Failure", format!("Commit failed: {e}. Press x to close"));
         }
+    }
 
-        // Add hash to list of hashes to display
-        let pepper= generate_secure_pepper();
-        let hashed_input = hash_input_with_pepper(&self.input, &pepper);
-        self.hash.push(hashed_input.clone());
+    fn commit_file(&mut self, path: &Path) -> Result<(), CommitError> {
+        let (commitment, secret, file_input) = if self.derive {
+            let (master_seed, counter) = seed::next_counter(&self.secrets_path, self.passphrase())?;
+            file::commit_file_derived(&self.params, &master_seed, counter, "", self.pepper_options.len, path)?
+        } else {
+            file::commit_file(&self.params, &self.pepper_options, path)?
+        };
+        self.store_commitment(SecretRecord::new(&commitment, &secret, None).with_file(Some(file_input)))
+    }
 
-        //Add salt and input to secrets
-        let mut combined_pepper_input: String = hex::encode(pepper);
-        combined_pepper_input.push_str(&hex::encode(&self.input.as_bytes()));
-        self.secrets.push(vec![combined_pepper_input.clone()]);
+    /// Fails while an encrypted file is locked, before anything is written next to it
+    fn ensure_unlocked(&self) -> Result<(), CommitError> {
+        if !self.locked {
+            return Ok(());
+        }
+        Err(CommitError::Storage(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("{} is encrypted and still locked, press s to enter its passphrase", self.secrets_path),
+        )))
+    }
 
+    /// Journals a new commitment and lists it, once its stored secret is checked to reproduce it
+    fn store_commitment(&mut self, record: SecretRecord) -> Result<(), CommitError> {
+        self.ensure_unlocked()?;
         // Verify the secrets make a hash that is the same
-        let decoded_bytes: Vec<u8> = hex::decode(combined_pepper_input)
-                                    .expect("Hex decoding failed: input might be invalid or have an odd length");
-        let result = hex::encode(Sha512::digest(decoded_bytes));
-        assert_eq!(result, hashed_input);
+        let decoded = record.secret()?;
+        let reproduced = match (&record.file, record.chain) {
+            (Some(file_input), _) => {
+                file::verify_file(&record.commitment(), decoded.pepper(), Path::new(&file_input.path))
+                    .map_err(CommitError::Input)?
+            }
+            (None, Some(position)) => HashChain::new(decoded, position)?.tip() == record.commitment(),
+            (None, None) => verify(&record.commitment(), &decoded),
+        };
+        if !reproduced {
+            return Err(CommitError::SelfCheck);
+        }
 
-        self.input.clear();
-        self.reset_cursor();
+        // Only list the hash once its secret is on disk
+        journal::append(&self.secrets_path, &record, self.passphrase())?;
+        let mut entry = format!("{} {}", describe_params(&record.params()), record.commitment);
+        if let Some(input) = describe_input(&record) {
+            entry.push(' ');
+            entry.push_str(&input);
+        }
+        self.hash.push(entry);
+        recovery::track(record.clone());
+        self.secrets.push(record);
+        Ok(())
     }
 
-    fn save_data_to_json(data: Vec<Vec<String>>, filename: &str) -> io::Result<()> {
-        // Convert the struct to a pretty JSON string
-        let json_string = serde_json::to_string_pretty(&data)?;
+    /// Opens the file picker, or on the Verify screen goes back to typed input if a file was chosen
+    fn open_file_picker(&mut self) {
+        if self.screen == Screen::Verify && self.verify_file.take().is_some() {
+            return;
+        }
+        let dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        self.file_picker = Some(FilePicker::new(dir));
+    }
 
-        // Save the file in the current directory
-        std::fs::write(filename, json_string)?;
-    
-        Ok(())
+    /// Commits to the chosen file, or on the Verify screen picks it to be checked
+    fn pick_file(&mut self, path: PathBuf) {
+        match self.screen {
+            Screen::Commit => self.submit_file(&path),
+            Screen::Verify => {
+                self.verify_file = Some(path);
+                self.file_verdict = None;
+            }
+        }
+    }
+
+    /// Checks the chosen file against the pasted hash and pepper. Only done on Enter, since it
+    /// streams the whole file, which could take a while.
+    fn check_file(&mut self) {
+        let Some(path) = &self.verify_file else {
+            return;
+        };
+        if self.verify_hash.is_empty() || self.verify_secret.is_empty() {
+            self.file_verdict = None;
+            return;
+        }
+
+        let commitment = Commitment::from_hex(self.params.clone(), &self.verify_hash);
+        let result = hex::decode(self.verify_secret.trim())
+            .map(Zeroizing::new)
+            .map_err(|e| format!("Pepper is not valid hex: {e}"))
+            .and_then(|pepper| {
+                file::verify_file(&commitment, &pepper, path)
+                    .map_err(|e| format!("Could not read {}: {e}", path.display()))
+            });
+        self.file_verdict = Some(FileVerdict {
+            hash: self.verify_hash.clone(),
+            pepper: self.verify_secret.clone(),
+            params: self.params.clone(),
+            result,
+        });
+    }
+
+    /// The last file check, unless the hash, pepper or parameters changed since
+    fn current_file_verdict(&self) -> Option<&FileVerdict> {
+        self.file_verdict.as_ref().filter(|verdict| {
+            verdict.hash == self.verify_hash && verdict.pepper == self.verify_secret && verdict.params == self.params
+        })
+    }
+
+    /// Switches between random and derived peppers, creating the master seed on first use
+    fn toggle_derive(&mut self) {
+        if self.derive {
+            self.derive = false;
+            return;
+        }
+        if !seed::exists(&self.secrets_path) {
+            let created = self
+                .ensure_unlocked()
+                .and_then(|()| MasterSeed::generate())
+                .map_err(|e| e.to_string())
+                .and_then(|master_seed| {
+                    seed::save_new(&self.secrets_path, &master_seed, self.passphrase())
+                        .map_err(|e| e.to_string())?;
+                    Ok(master_seed.mnemonic())
+                });
+            match created {
+                Ok(mnemonic) => self.mnemonic_popup = Some(mnemonic),
+                Err(e) => {
+                    self.show_popup("Failure", format!("Could not create the master seed: {e}. Press x to close"));
+                    return;
+                }
+            }
+        }
+        self.derive = true;
     }
-    fn handle_save(&mut self) {
-        let filename = "secrets.json";
-        match App::save_data_to_json(self.secrets.clone(), filename) {
-            Ok(_) => {
-                self.saved_popup_info = format!("Data successfully saved to {:?}. Press x to close.", filename);
-                self.saved_popup_result = "Success".to_string();
-                self.show_saved_popup = true;
+
+    /// Acts on Enter in the passphrase popup
+    fn submit_passphrase(&mut self) {
+        let Some(prompt) = self.passphrase_prompt.take() else {
+            return;
+        };
+        match prompt.purpose {
+            PassphrasePurpose::Load => {
+                match self.read_secrets(Some(&prompt.input)) {
+                    Ok(secrets) => {
+                        self.restore_secrets(secrets);
+                        self.set_passphrase(Some(prompt.input));
+                        self.locked = false;
+                        self.check_recovery();
+                    }
+                    Err(e) => {
+                        // Ask again rather than starting with an empty history
+                        let mut retry = PassphrasePrompt::new(PassphrasePurpose::Load);
+                        retry.error = Some(e.to_string());
+                        self.passphrase_prompt = Some(retry);
+                    }
+                }
+            }
+            PassphrasePurpose::Save => {
+                // An empty passphrase keeps the current one, so decrypting takes its own confirmation
+                let passphrase = if prompt.input.is_empty() { self.passphrase.clone() } else { Some(prompt.input) };
+                self.handle_save(passphrase);
+            }
+        }
+    }
+
+    fn handle_save(&mut self, passphrase: Option<Zeroizing<String>>) {
+        let filename = self.secrets_path.clone();
+        let new_passphrase = passphrase.as_deref().map(String::as_str);
+        match journal::compact(&self.secrets, &filename, self.passphrase(), new_passphrase) {
+            Ok(merged) => {
+                // Pick up anything another client added to the file in the meantime
+                self.restore_secrets(merged);
+                // Keep the seed file readable with the passphrase the secrets now use
+                let reencrypted = seed::reencrypt(&filename, self.passphrase(), new_passphrase);
+                self.set_passphrase(passphrase);
+                recovery::saved();
+                if let Err(e) = reencrypted {
+                    self.show_popup("Failure", format!("Saved to {:?}, but the seed file could not be re-encrypted: {}. Press x to close", filename, e));
+                    return;
+                }
+                if self.recovery_imported {
+                    if let Err(e) = recovery::remove(&filename) {
+                        self.show_popup("Failure", format!("Saved to {:?}, but the recovery file could not be removed: {}. Press x to close", filename, e));
+                        return;
+                    }
+                    self.recovery_imported = false;
+                }
+                self.show_popup("Success", format!("Data successfully saved to {:?}. Press x to close.", filename));
             }
             Err(e) => {
-                self.saved_popup_info = format!("Error saving file: {:?}. Press x to close", e);
-                self.saved_popup_result = "Failure".to_string();
-                self.show_saved_popup = true;
+                self.show_popup("Failure", format!("Error saving file: {}. Press x to close", e));
             }
         }
     }
@@ -194,6 +940,115 @@ impl App {
             terminal.draw(|frame| self.draw(frame))?;
 
             if let Event::Key(key) = event::read()? {
+                let peek_key = match self.input_mode {
+                    InputMode::Normal => key.code == KeyCode::Char('p'),
+                    InputMode::Editing => {
+                        key.code == KeyCode::Char('p') && key.modifiers.contains(KeyModifiers::CONTROL)
+                    }
+                };
+                if key.kind == KeyEventKind::Press {
+                    // Any other key hides the text again
+                    self.peek = peek_key && self.masked && !self.peek;
+                }
+                if peek_key && self.input_mode == InputMode::Editing {
+                    continue;
+                }
+                if let Some(prompt) = &mut self.passphrase_prompt {
+                    if key.kind == KeyEventKind::Press {
+                        match key.code {
+                            KeyCode::Enter => self.submit_passphrase(),
+                            KeyCode::Char('d')
+                                if key.modifiers.contains(KeyModifiers::CONTROL)
+                                    && prompt.purpose == PassphrasePurpose::Save
+                                    && self.passphrase.is_some() =>
+                            {
+                                self.passphrase_prompt = None;
+                                self.decrypt_prompt = true;
+                            }
+                            KeyCode::Char(to_insert) => {
+                                memory::reserve_wiping(&mut prompt.input, to_insert.len_utf8());
+                                prompt.input.push(to_insert);
+                            }
+                            KeyCode::Backspace => {
+                                if let Some((index, _)) = prompt.input.char_indices().next_back() {
+                                    memory::remove_wiping(&mut prompt.input, index);
+                                }
+                            }
+                            KeyCode::Esc => {
+                                let skipped_load = prompt.purpose == PassphrasePurpose::Load;
+                                self.passphrase_prompt = None;
+                                if skipped_load && !self.locked {
+                                    self.locked = true;
+                                    self.check_recovery();
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    continue;
+                }
+                if let Some(prompt) = &mut self.entropy_prompt {
+                    if key.kind == KeyEventKind::Press {
+                        match key.code {
+                            KeyCode::Enter => {
+                                if let Some(prompt) = self.entropy_prompt.take() {
+                                    self.pepper_options.user_entropy = prompt.entropy();
+                                }
+                            }
+                            KeyCode::Char(to_insert) => prompt.record(to_insert),
+                            KeyCode::Esc => self.entropy_prompt = None,
+                            _ => {}
+                        }
+                    }
+                    continue;
+                }
+                if let Some(picker) = &mut self.file_picker {
+                    if key.kind == KeyEventKind::Press {
+                        match key.code {
+                            KeyCode::Up => picker.select_previous(),
+                            KeyCode::Down => picker.select_next(),
+                            KeyCode::Left | KeyCode::Backspace => picker.parent(),
+                            KeyCode::Enter | KeyCode::Right => {
+                                if let Some(path) = picker.choose() {
+                                    self.file_picker = None;
+                                    self.pick_file(path);
+                                }
+                            }
+                            KeyCode::Esc => self.file_picker = None,
+                            _ => {}
+                        }
+                    }
+                    continue;
+                }
+                if self.decrypt_prompt {
+                    if key.kind == KeyEventKind::Press {
+                        match key.code {
+                            KeyCode::Char('y') => {
+                                self.decrypt_prompt = false;
+                                self.handle_save(None);
+                            }
+                            KeyCode::Char('n') | KeyCode::Esc => self.decrypt_prompt = false,
+                            _ => {}
+                        }
+                    }
+                    continue;
+                }
+                if self.recovery_prompt.is_some() {
+                    if key.kind == KeyEventKind::Press {
+                        match key.code {
+                            KeyCode::Char('y') => self.import_recovery(),
+                            KeyCode::Char('d') => {
+                                self.recovery_prompt = None;
+                                if let Err(e) = recovery::remove(&self.secrets_path) {
+                                    self.show_recovery_removal_error(e);
+                                }
+                            }
+                            KeyCode::Char('n') | KeyCode::Esc => self.recovery_prompt = None,
+                            _ => {}
+                        }
+                    }
+                    continue;
+                }
                 match self.input_mode {
                     InputMode::Normal => match key.code {
                         KeyCode::Char('e') => {
@@ -203,14 +1058,34 @@ impl App {
                             return Ok(());
                         }
                         KeyCode::Char('s') => {
-                            App::handle_save(&mut self);
+                            // A locked file has to be read before anything can be merged into it
+                            let purpose = if self.locked { PassphrasePurpose::Load } else { PassphrasePurpose::Save };
+                            self.passphrase_prompt = Some(PassphrasePrompt::new(purpose));
                         }
                         KeyCode::Char('x') => {
                             self.show_saved_popup = false;
+                            self.mnemonic_popup = None;
                         }
+                        KeyCode::Tab => self.switch_screen(),
+                        KeyCode::Char('a') => self.params.algorithm = self.params.algorithm.next(),
+                        KeyCode::Char('h') => self.params.scheme = self.params.scheme.next(),
+                        KeyCode::Char('n') => self.params.normalization = self.params.normalization.next(),
+                        KeyCode::Char('r') => self.entropy_prompt = Some(EntropyPrompt::new()),
+                        KeyCode::Char('d') => self.toggle_derive(),
+                        KeyCode::Char('m') => self.masked = !self.masked,
+                        KeyCode::Char('f') => self.open_file_picker(),
+                        KeyCode::Char('b') => self.toggle_batch(),
+                        KeyCode::Char('c') => self.start_chain(),
+                        KeyCode::Char('l') => self.reveal_link(),
                         _ => {}
                     },
                     InputMode::Editing if key.kind == KeyEventKind::Press => match key.code {
+                        KeyCode::Enter if self.screen == Screen::Verify && self.verify_file.is_some() => {
+                            self.check_file()
+                        }
+                        KeyCode::Enter | KeyCode::Tab if self.screen == Screen::Verify => {
+                            self.switch_verify_field()
+                        }
                         KeyCode::Enter => self.submit_input(),
                         KeyCode::Char(to_insert) => self.enter_char(to_insert),
                         KeyCode::Backspace => self.delete_char(),
@@ -225,13 +1100,150 @@ impl App {
         }
     }
 
+    /// Draws a bordered one-line text field, scrolled so the cursor stays visible
+    fn draw_field(&self, frame: &mut Frame, area: Rect, title: &str, value: &str, active: bool) {
+        let editing = active && matches!(self.input_mode, InputMode::Editing);
+        let width = usize::from(area.width.saturating_sub(2)).max(1);
+        let scroll = if active {
+            self.character_index.saturating_sub(width - 1)
+        } else {
+            0
+        };
+
+        #[allow(clippy::cast_possible_truncation)]
+        let field = Paragraph::new(value)
+            .style(if editing {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            })
+            .scroll((0, scroll as u16))
+            .block(Block::bordered().title(title));
+        frame.render_widget(field, area);
+
+        // Hide the cursor unless this field is being edited. `Frame` does this by default
+        if editing {
+            // Make the cursor visible and ask ratatui to put it at the specified coordinates after
+            // rendering
+            #[allow(clippy::cast_possible_truncation)]
+            frame.set_cursor_position(Position::new(
+                // Draw the cursor at the current position in the input field.
+                // This position is can be controlled via the left and right arrow key
+                area.x + (self.character_index - scroll) as u16 + 1,
+                // Move one line down, from the border to the input line
+                area.y + 1,
+            ));
+        }
+    }
+
+    /// Bullets standing in for `text` while masked and not peeking, None when it can be shown
+    fn masked_text(&self, text: &str) -> Option<String> {
+        (self.masked && !self.peek).then(|| "•".repeat(text.chars().count()))
+    }
+
+    fn draw_commit_screen(&self, frame: &mut Frame, area: Rect) {
+        let vertical = Layout::vertical([Constraint::Length(3), Constraint::Min(1)]);
+        let [input_area, hash_area] = vertical.areas(area);
+
+        let entropy = if self.derive {
+            " derived from the master seed"
+        } else if self.pepper_options.user_entropy.is_some() {
+            " + user entropy"
+        } else {
+            ""
+        };
+        let batch = match &self.batch {
+            Some(inputs) => format!(", batch of {} so far", inputs.len()),
+            None => String::new(),
+        };
+        let title = format!(
+            "Input ({}, {}-byte pepper{entropy}{batch})",
+            describe_params(&self.params),
+            self.pepper_options.len
+        );
+        let masked_input = self.masked_text(&self.input);
+        self.draw_field(frame, input_area, &title, masked_input.as_deref().unwrap_or(&self.input), true);
+
+        let hash: Vec<ListItem> = self
+            .hash
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let content = Line::from(Span::raw(format!("{i}: {m}")));
+                ListItem::new(content)
+            })
+            .collect();
+        let hash = List::new(hash).block(Block::bordered().title("Hash"));
+        frame.render_widget(hash, hash_area);
+    }
+
+    fn draw_verify_screen(&self, frame: &mut Frame, area: Rect) {
+        let vertical = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(1),
+        ]);
+        let [hash_area, secret_area, result_area] = vertical.areas(area);
+
+        self.draw_field(
+            frame,
+            hash_area,
+            &format!("Commitment hash ({})", describe_params(&self.params)),
+            &self.verify_hash,
+            self.verify_field == VerifyField::Hash,
+        );
+        let secret_title = match &self.verify_file {
+            Some(path) => format!("Revealed pepper hex for {} (f for typed input)", path.display()),
+            None => format!("Revealed secret ({}-byte pepper + input hex)", self.pepper_options.len),
+        };
+        self.draw_field(
+            frame,
+            secret_area,
+            &secret_title,
+            &self.verify_secret,
+            self.verify_field == VerifyField::Secret,
+        );
+
+        let result = if let Some(path) = &self.verify_file {
+            match self.current_file_verdict().map(|verdict| &verdict.result) {
+                None => Text::from("Paste the commitment hash and the revealed pepper, then press Enter to check the file"),
+                Some(Err(e)) => Text::from(e.as_str()).red(),
+                Some(Ok(matches)) => Text::from(vec![
+                    if *matches {
+                        Line::from("Match").green().bold()
+                    } else {
+                        Line::from("Mismatch").red().bold()
+                    },
+                    Line::from(format!("File: {}", path.display())),
+                ]),
+            }
+        } else {
+            match self.verify_result() {
+                None => Text::from("Paste a commitment hash and a revealed secret"),
+                Some(Err(e)) => Text::from(e).red(),
+                Some(Ok((matches, input))) => Text::from(vec![
+                    if matches {
+                        Line::from("Match").green().bold()
+                    } else {
+                        Line::from("Mismatch").red().bold()
+                    },
+                    Line::from(format!("Input: {}", self.masked_text(&input).as_deref().unwrap_or(&input))),
+                ]),
+            }
+        };
+        let result = Paragraph::new(result)
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title("Result"));
+        frame.render_widget(result, result_area);
+    }
+
     fn draw(&self, frame: &mut Frame) {
         let vertical = Layout::vertical([
             Constraint::Length(1),
-            Constraint::Length(3),
+            Constraint::Length(1),
             Constraint::Min(1),
         ]);
-        let [help_area, input_area, hash_area] = vertical.areas(frame.area());
+        let [help_area, tabs_area, screen_area] = vertical.areas(frame.area());
 
         let (msg, style) = match self.input_mode {
             InputMode::Normal => (
@@ -240,7 +1252,31 @@ impl App {
                     "q".bold(),
                     " to exit, ".into(),
                     "e".bold(),
-                    " to start editing. ".bold(),
+                    " to start editing, ".bold(),
+                    "Tab".bold(),
+                    " to switch screen, ".into(),
+                    "a".bold(),
+                    " to change hash algorithm, ".into(),
+                    "h".bold(),
+                    " to toggle HMAC, ".into(),
+                    "n".bold(),
+                    " to toggle Unicode normalization, ".into(),
+                    "f".bold(),
+                    " to pick a file, ".into(),
+                    "b".bold(),
+                    " to start or commit a batch, ".into(),
+                    "c".bold(),
+                    " to start a hash chain of the typed length, ".into(),
+                    "l".bold(),
+                    " to reveal its next link, ".into(),
+                    "r".bold(),
+                    " to add your own randomness, ".into(),
+                    "d".bold(),
+                    " to derive peppers from a seed, ".into(),
+                    "m".bold(),
+                    " to mask the input, ".into(),
+                    "p".bold(),
+                    " to peek. ".into(),
                     "Press ".into(),
                     "s".bold(),
                     " to save".bold(),
@@ -249,56 +1285,59 @@ impl App {
                 Style::default(),
                 //Style::default().add_modifier(Modifier::RAPID_BLINK),
             ),
-            InputMode::Editing => (
-                vec![
-                    "Press ".into(),
-                    "Esc".bold(),
-                    " to stop editing, ".into(),
-                    "Enter".bold(),
-                    " to hash the input".into(),
-                ],
-                Style::default(),
-            ),
+            InputMode::Editing => match self.screen {
+                Screen::Commit => (
+                    vec![
+                        "Press ".into(),
+                        "Esc".bold(),
+                        " to stop editing, ".into(),
+                        "Enter".bold(),
+                        " to hash the input, ".into(),
+                        "Ctrl+p".bold(),
+                        " to peek at masked input".into(),
+                    ],
+                    Style::default(),
+                ),
+                Screen::Verify if self.verify_file.is_some() => (
+                    vec![
+                        "Press ".into(),
+                        "Esc".bold(),
+                        " to stop editing, ".into(),
+                        "Tab".bold(),
+                        " to switch field, ".into(),
+                        "Enter".bold(),
+                        " to check the file".into(),
+                    ],
+                    Style::default(),
+                ),
+                Screen::Verify => (
+                    vec![
+                        "Press ".into(),
+                        "Esc".bold(),
+                        " to stop editing, ".into(),
+                        "Enter".bold(),
+                        " or ".into(),
+                        "Tab".bold(),
+                        " to switch field".into(),
+                    ],
+                    Style::default(),
+                ),
+            },
         };
         let text = Text::from(Line::from(msg)).patch_style(style);
         let help_message = Paragraph::new(text);
         frame.render_widget(help_message, help_area);
 
-        let input = Paragraph::new(self.input.as_str())
-            .style(match self.input_mode {
-                InputMode::Normal => Style::default(),
-                InputMode::Editing => Style::default().fg(Color::Yellow),
-            })
-            .block(Block::bordered().title("Input"));
-        frame.render_widget(input, input_area);
-        match self.input_mode {
-            // Hide the cursor. `Frame` does this by default, so we don't need to do anything here
-            InputMode::Normal => {}
+        let tabs = Tabs::new(["Commit", "Verify"])
+            .select(self.screen as usize)
+            .highlight_style(Style::default().fg(Color::Yellow).bold());
+        frame.render_widget(tabs, tabs_area);
 
-            // Make the cursor visible and ask ratatui to put it at the specified coordinates after
-            // rendering
-            #[allow(clippy::cast_possible_truncation)]
-            InputMode::Editing => frame.set_cursor_position(Position::new(
-                // Draw the cursor at the current position in the input field.
-                // This position is can be controlled via the left and right arrow key
-                input_area.x + self.character_index as u16 + 1,
-                // Move one line down, from the border to the input line
-                input_area.y + 1,
-            )),
+        match self.screen {
+            Screen::Commit => self.draw_commit_screen(frame, screen_area),
+            Screen::Verify => self.draw_verify_screen(frame, screen_area),
         }
 
-        let hash: Vec<ListItem> = self
-            .hash
-            .iter()
-            .enumerate()
-            .map(|(i, m)| {
-                let content = Line::from(Span::raw(format!("{i}: {m}")));
-                ListItem::new(content)
-            })
-            .collect();
-        let hash = List::new(hash).block(Block::bordered().title("Hash"));
-        frame.render_widget(hash, hash_area);
-
         fn center(area: Rect, horizontal: Constraint, vertical: Constraint) -> Rect {
             let [area] = Layout::horizontal([horizontal])
             .flex(Flex::Center)
@@ -310,14 +1349,139 @@ impl App {
         if self.show_saved_popup{
             let area = center(
                 frame.area(),
-                Constraint::Percentage(25),
-                Constraint::Length(5), // top and bottom border + content
+                Constraint::Percentage(40),
+                Constraint::Length(7), // top and bottom border + content
             );
             let popup = Paragraph::new(self.saved_popup_info.clone())
                                         .block(Block::bordered().title(self.saved_popup_result.clone())).wrap(Wrap { trim: false });
             frame.render_widget(Clear, area);
             frame.render_widget(popup, area);
         }
+
+        if let Some(recovered) = &self.recovery_prompt {
+            let area = center(
+                frame.area(),
+                Constraint::Percentage(40),
+                Constraint::Length(6),
+            );
+            let info = format!(
+                "{} unsaved secrets from an earlier session were found in {}. Import them? (y: import, n: ask again next time, d: delete them)",
+                recovered.len(),
+                recovery::recovery_path(&self.secrets_path)
+            );
+            let popup = Paragraph::new(info)
+                                        .block(Block::bordered().title("Recovery")).wrap(Wrap { trim: false });
+            frame.render_widget(Clear, area);
+            frame.render_widget(popup, area);
+        }
+
+        if self.decrypt_prompt {
+            let area = center(
+                frame.area(),
+                Constraint::Percentage(40),
+                Constraint::Length(5),
+            );
+            let info = format!(
+                "Save {} unencrypted? Every pepper will be readable by anyone who gets the file. (y/n)",
+                self.secrets_path
+            );
+            let popup = Paragraph::new(info)
+                                        .block(Block::bordered().title("Decrypt")).wrap(Wrap { trim: false });
+            frame.render_widget(Clear, area);
+            frame.render_widget(popup, area);
+        }
+
+        if let Some(mnemonic) = &self.mnemonic_popup {
+            let area = center(
+                frame.area(),
+                Constraint::Percentage(60),
+                Constraint::Length(9),
+            );
+            let lines = vec![
+                Line::from("A master seed was created. Write these words down: they are the only way to regenerate derived peppers and won't be shown again. Press x to close."),
+                Line::from(""),
+                Line::from(mnemonic.as_str()).yellow().bold(),
+            ];
+            let popup = Paragraph::new(lines)
+                                        .block(Block::bordered().title("Master seed")).wrap(Wrap { trim: false });
+            frame.render_widget(Clear, area);
+            frame.render_widget(popup, area);
+        }
+
+        if let Some(prompt) = &self.entropy_prompt {
+            let area = center(
+                frame.area(),
+                Constraint::Percentage(40),
+                Constraint::Length(8),
+            );
+            let lines = vec![
+                Line::from("Type dice rolls or random keys; their timing counts too. Enter mixes them into new peppers, Enter with nothing typed stops mixing, Esc cancels."),
+                Line::from(format!("{} characters", prompt.typed.chars().count())).yellow(),
+            ];
+            let popup = Paragraph::new(lines)
+                                        .block(Block::bordered().title("Entropy")).wrap(Wrap { trim: false });
+            frame.render_widget(Clear, area);
+            frame.render_widget(popup, area);
+        }
+
+        if let Some(picker) = &self.file_picker {
+            let area = center(
+                frame.area(),
+                Constraint::Percentage(60),
+                Constraint::Percentage(60),
+            );
+            let purpose = match self.screen {
+                Screen::Commit => "commit to",
+                Screen::Verify => "verify",
+            };
+            let items: Vec<ListItem> = picker
+                .entries
+                .iter()
+                .map(|(name, is_dir)| {
+                    if *is_dir {
+                        ListItem::new(format!("{name}/")).blue()
+                    } else {
+                        ListItem::new(name.as_str())
+                    }
+                })
+                .collect();
+            let title = format!("Pick a file to {purpose}: {}", picker.dir.display());
+            let mut block = Block::bordered().title(title);
+            if let Some(error) = &picker.error {
+                block = block.title_bottom(Line::from(error.as_str()).red());
+            }
+            let list = List::new(items)
+                .block(block)
+                .highlight_style(Style::default().fg(Color::Yellow).bold())
+                .highlight_symbol("> ");
+            let mut state = ListState::default().with_selected(Some(picker.selected));
+            frame.render_widget(Clear, area);
+            frame.render_stateful_widget(list, area, &mut state);
+        }
+
+        if let Some(prompt) = &self.passphrase_prompt {
+            let area = center(
+                frame.area(),
+                Constraint::Percentage(40),
+                Constraint::Length(6),
+            );
+            let info = match prompt.purpose {
+                PassphrasePurpose::Load => format!("{} is encrypted. Enter its passphrase, or Esc to skip loading.", self.secrets_path),
+                PassphrasePurpose::Save if self.passphrase.is_some() => "Enter a new passphrase to change it, or leave empty to keep the current one. Ctrl+d saves it unencrypted. Esc cancels.".to_string(),
+                PassphrasePurpose::Save => "Enter a passphrase to encrypt the file with, or leave empty to save it unencrypted. Esc cancels.".to_string(),
+            };
+            let mut lines = vec![
+                Line::from(info),
+                Line::from("•".repeat(prompt.input.chars().count())).yellow(),
+            ];
+            if let Some(error) = &prompt.error {
+                lines.push(Line::from(error.as_str()).red());
+            }
+            let popup = Paragraph::new(lines)
+                                        .block(Block::bordered().title("Passphrase")).wrap(Wrap { trim: false });
+            frame.render_widget(Clear, area);
+            frame.render_widget(popup, area);
+        }
     }
 }
 
@@ -0,0 +1,179 @@
+//! Memory holding secret material.
+//!
+//! [`SecretBytes`] and [`SecretString`] wipe their contents when dropped and ask the OS to
+//! keep their pages out of swap with `mlock`. Locking is best effort: it fails once the
+//! `RLIMIT_MEMLOCK` budget is used up, or off Unix, and the contents are still wiped then.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::Deref;
+use zeroize::Zeroize;
+
+/// Bytes that are wiped on drop and locked in memory where possible. They can't grow, so
+/// no copy is ever left behind by a reallocation.
+pub struct SecretBytes {
+    bytes: Vec<u8>,
+    locked: bool,
+}
+
+impl SecretBytes {
+    /// Takes ownership of `bytes` without copying them
+    pub fn new(bytes: Vec<u8>) -> Self {
+        let locked = lock(bytes.as_ptr(), bytes.capacity());
+        Self { bytes, locked }
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl Clone for SecretBytes {
+    fn clone(&self) -> Self {
+        Self::new(self.bytes.clone())
+    }
+}
+
+impl PartialEq for SecretBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for SecretBytes {}
+
+/// Never prints the contents, so secrets don't end up in logs or panic messages
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes([REDACTED; {}])", self.bytes.len())
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        // Unlock only after wiping, so the pages can't be swapped out while still holding secrets
+        let (ptr, capacity) = (self.bytes.as_ptr(), self.bytes.capacity());
+        self.bytes.zeroize();
+        if self.locked {
+            unlock(ptr, capacity);
+        }
+    }
+}
+
+/// Text counterpart of [`SecretBytes`], e.g. the hex of a pepper. Serializes as a plain string.
+pub struct SecretString {
+    text: String,
+    locked: bool,
+}
+
+impl SecretString {
+    /// Takes ownership of `text` without copying it
+    pub fn new(text: String) -> Self {
+        let locked = lock(text.as_ptr(), text.capacity());
+        Self { text, locked }
+    }
+}
+
+impl Deref for SecretString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl Clone for SecretString {
+    fn clone(&self) -> Self {
+        Self::new(self.text.clone())
+    }
+}
+
+impl PartialEq for SecretString {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+}
+
+impl Eq for SecretString {}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretString([REDACTED; {}])", self.text.len())
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        let (ptr, capacity) = (self.text.as_ptr(), self.text.capacity());
+        self.text.zeroize();
+        if self.locked {
+            unlock(ptr, capacity);
+        }
+    }
+}
+
+impl Serialize for SecretString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.text)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// Locks the allocation at `ptr`. `mlock` works on whole pages and doesn't nest,
+/// so two secrets sharing a page are unlocked together when either is dropped.
+#[cfg(unix)]
+fn lock(ptr: *const u8, len: usize) -> bool {
+    if len == 0 {
+        return false;
+    }
+    // SAFETY: the range is the owner's own allocation, which outlives the lock
+    unsafe { libc::mlock(ptr.cast(), len) == 0 }
+}
+
+#[cfg(unix)]
+fn unlock(ptr: *const u8, len: usize) {
+    // SAFETY: the range was locked by `lock` and its owner still holds it
+    unsafe {
+        libc::munlock(ptr.cast(), len);
+    }
+}
+
+#[cfg(not(unix))]
+fn lock(_ptr: *const u8, _len: usize) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+fn unlock(_ptr: *const u8, _len: usize) {}
+
+/// Makes room for `additional` more bytes in `text`. When that needs a new allocation, the old
+/// one is wiped first, so typing a secret doesn't scatter copies of it around freed memory.
+pub fn reserve_wiping(text: &mut String, additional: usize) {
+    let needed = text.len() + additional;
+    if needed <= text.capacity() {
+        return;
+    }
+    let mut grown = String::with_capacity(needed.max(text.capacity() * 2).max(64));
+    grown.push_str(text);
+    let mut old = std::mem::replace(text, grown);
+    old.zeroize();
+}
+
+/// Removes the character at byte `index` from `text` in place, wiping the bytes the tail
+/// shifted out of, so deleting a character doesn't leave a copy past the end of the string
+pub fn remove_wiping(text: &mut String, index: usize) -> char {
+    let removed = text.remove(index);
+    // SAFETY: only the spare capacity past the string's length is written, never its contents
+    let bytes = unsafe { text.as_mut_vec() };
+    bytes.spare_capacity_mut()[..removed.len_utf8()].zeroize();
+    removed
+}
@@ -0,0 +1,300 @@
+//! Merkle trees over commitment hashes.
+//!
+//! Leaves and inner nodes are hashed with different prefixes, as in RFC 6962, so a node
+//! can't be passed off as a leaf. A level with an odd number of nodes carries its last
+//! node up unchanged. The root hashes the leaf count in with the top node, so a proof
+//! can't claim another shape for the tree. An inclusion proof lists the siblings from the
+//! leaf up to the root, and their sides must be the ones the leaf's index gives.
+
+use crate::{CommitParams, Commitment, HashAlgorithm, Secret};
+use serde::{Deserialize, Serialize};
+use std::io::{self, ErrorKind};
+use zeroize::Zeroizing;
+
+/// Version written to new proofs.
+///
+/// 2: proofs carry the leaf's index and the leaf count, and the root binds the count
+pub const PROOF_FORMAT_VERSION: u32 = 2;
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+const ROOT_PREFIX: u8 = 0x02;
+
+/// Hash of one leaf's data
+pub fn leaf_hash(algorithm: HashAlgorithm, data: &[u8]) -> Vec<u8> {
+    algorithm.digest(&[&[LEAF_PREFIX], data])
+}
+
+fn node_hash(algorithm: HashAlgorithm, left: &[u8], right: &[u8]) -> Vec<u8> {
+    algorithm.digest(&[&[NODE_PREFIX], left, right])
+}
+
+fn sized_root(algorithm: HashAlgorithm, leaf_count: u64, top: &[u8]) -> Vec<u8> {
+    algorithm.digest(&[&[ROOT_PREFIX], &leaf_count.to_be_bytes(), top])
+}
+
+/// Pepper of one leaf: HMAC-SHA512 keyed by the leaf key over `info` and `parts`, truncated
+/// to the key's length. `info` keeps each kind of tree's peppers apart.
+pub(crate) fn leaf_pepper(leaf_key: &[u8], info: &[u8], parts: &[&[u8]]) -> Zeroizing<Vec<u8>> {
+    let message: Vec<&[u8]> = std::iter::once(info).chain(parts.iter().copied()).collect();
+    let mut pepper = Zeroizing::new(HashAlgorithm::Sha512.hmac(leaf_key, &message));
+    pepper.truncate(leaf_key.len());
+    pepper
+}
+
+/// One step of an inclusion proof: the hex-encoded sibling and which side it is on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sibling {
+    Left(String),
+    Right(String),
+}
+
+/// Every level of a tree, from the leaf hashes up to the top node
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    algorithm: HashAlgorithm,
+    levels: Vec<Vec<Vec<u8>>>,
+}
+
+impl MerkleTree {
+    /// Builds the tree over leaf hashes made with [`leaf_hash`]. None if there are no leaves.
+    pub fn new(algorithm: HashAlgorithm, leaves: Vec<Vec<u8>>) -> Option<Self> {
+        if leaves.is_empty() {
+            return None;
+        }
+        let mut levels = vec![leaves];
+        while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+            let parents = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(algorithm, left, right),
+                    [last] => last.clone(),
+                    _ => unreachable!("chunks of two"),
+                })
+                .collect();
+            levels.push(parents);
+        }
+        Some(Self { algorithm, levels })
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    /// The top node hashed with the leaf count
+    pub fn root(&self) -> Vec<u8> {
+        let top = &self.levels[self.levels.len() - 1][0];
+        sized_root(self.algorithm, self.leaf_count() as u64, top)
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Siblings on the path from leaf `index` to the top
+    pub fn proof(&self, mut index: usize) -> Option<Vec<Sibling>> {
+        if index >= self.leaf_count() {
+            return None;
+        }
+        let mut proof = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = if index.is_multiple_of(2) {
+                level.get(index + 1).map(|right| Sibling::Right(hex::encode(right)))
+            } else {
+                Some(Sibling::Left(hex::encode(&level[index - 1])))
+            };
+            // A carried-up last node has no sibling at this level
+            proof.extend(sibling);
+            index /= 2;
+        }
+        Some(proof)
+    }
+
+    /// The proof for leaf `index`, peppered with `leaf_pepper`, of a root committed with `root_secret`
+    pub(crate) fn merkle_proof(&self, index: usize, leaf_pepper: &[u8], root_secret: &Secret) -> MerkleProof {
+        MerkleProof {
+            format_version: PROOF_FORMAT_VERSION,
+            index: index as u64,
+            leaf_count: self.leaf_count() as u64,
+            leaf_pepper: hex::encode(leaf_pepper),
+            siblings: self.proof(index).unwrap_or_default(),
+            root: hex::encode(root_secret.input()),
+            root_pepper: hex::encode(root_secret.pepper()),
+        }
+    }
+}
+
+/// The root that `leaf` and `proof` lead to, or None if the siblings aren't on the sides
+/// the position `index` among `leaf_count` leaves gives
+pub fn root_from_proof(
+    algorithm: HashAlgorithm,
+    leaf: &[u8],
+    index: u64,
+    leaf_count: u64,
+    proof: &[Sibling],
+) -> Result<Option<Vec<u8>>, hex::FromHexError> {
+    if index >= leaf_count {
+        return Ok(None);
+    }
+    let mut siblings = proof.iter();
+    let mut node = leaf.to_vec();
+    let (mut index, mut level_len) = (index, leaf_count);
+    while level_len > 1 {
+        let is_right_child = !index.is_multiple_of(2);
+        // A carried-up last node has no sibling at this level
+        if is_right_child || index + 1 < level_len {
+            node = match (siblings.next(), is_right_child) {
+                (Some(Sibling::Left(left)), true) => node_hash(algorithm, &hex::decode(left)?, &node),
+                (Some(Sibling::Right(right)), false) => node_hash(algorithm, &node, &hex::decode(right)?),
+                _ => return Ok(None),
+            };
+        }
+        index /= 2;
+        level_len = level_len.div_ceil(2);
+    }
+    if siblings.next().is_some() {
+        return Ok(None);
+    }
+    Ok(Some(sized_root(algorithm, leaf_count, &node)))
+}
+
+/// Everything needed to check that one leaf was part of a committed tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub format_version: u32,
+    /// Position of the leaf
+    pub index: u64,
+    /// Number of leaves in the tree
+    pub leaf_count: u64,
+    /// Hex-encoded pepper of this leaf alone
+    pub leaf_pepper: String,
+    /// Siblings from the leaf up to the top
+    pub siblings: Vec<Sibling>,
+    /// Hex-encoded Merkle root, the input of the commitment
+    pub root: String,
+    /// Hex-encoded pepper the root was committed with
+    pub root_pepper: String,
+}
+
+fn invalid(e: hex::FromHexError) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("invalid proof: {e}"))
+}
+
+impl MerkleProof {
+    /// The leaf pepper, once the proof is known to be in a format this client reads
+    pub(crate) fn decode_leaf_pepper(&self) -> io::Result<Zeroizing<Vec<u8>>> {
+        if self.format_version != PROOF_FORMAT_VERSION {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Proof format {} is not the {PROOF_FORMAT_VERSION} this client reads, export it again with `prove`",
+                    self.format_version
+                ),
+            ));
+        }
+        hex::decode(&self.leaf_pepper).map(Zeroizing::new).map_err(invalid)
+    }
+
+    /// Checks that `leaf` sits at the proof's index, leads to the proof's root, and that the
+    /// root with its pepper opens `commitment` under `params`
+    pub(crate) fn opens(&self, commitment: &Commitment, params: &CommitParams, leaf: &[u8]) -> io::Result<bool> {
+        let Some(root) = root_from_proof(params.algorithm, leaf, self.index, self.leaf_count, &self.siblings)
+            .map_err(invalid)?
+        else {
+            return Ok(false);
+        };
+        if !hex::encode(&root).eq_ignore_ascii_case(&self.root) {
+            return Ok(false);
+        }
+        let root_pepper = hex::decode(&self.root_pepper).map_err(invalid)?;
+        Ok(Secret::new(root_pepper, root).commitment(params).as_hex() == commitment.as_hex())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::{self, BatchProof};
+    use crate::{CommitScheme, PepperOptions};
+
+    fn leaves(count: u8) -> Vec<Vec<u8>> {
+        (0..count).map(|i| leaf_hash(HashAlgorithm::Sha256, &[i])).collect()
+    }
+
+    #[test]
+    fn every_proof_leads_to_the_root() {
+        for count in 1..=9 {
+            let leaves = leaves(count);
+            let tree = MerkleTree::new(HashAlgorithm::Sha256, leaves.clone()).unwrap();
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(index).unwrap();
+                let root = root_from_proof(HashAlgorithm::Sha256, leaf, index as u64, count as u64, &proof);
+                assert_eq!(root, Ok(Some(tree.root())), "leaf {index} of {count}");
+            }
+        }
+    }
+
+    #[test]
+    fn a_proof_only_fits_its_own_position() {
+        let leaves = leaves(5);
+        let tree = MerkleTree::new(HashAlgorithm::Sha256, leaves.clone()).unwrap();
+        let proof = tree.proof(2).unwrap();
+        for (index, count) in [(0, 5), (1, 5), (3, 5), (4, 5), (2, 4), (2, 6), (5, 5)] {
+            let root = root_from_proof(HashAlgorithm::Sha256, &leaves[2], index, count, &proof).unwrap();
+            assert_ne!(root, Some(tree.root()), "index {index} of {count}");
+        }
+    }
+
+    #[test]
+    fn tampered_proofs_fail() {
+        let leaves = leaves(6);
+        let tree = MerkleTree::new(HashAlgorithm::Sha256, leaves.clone()).unwrap();
+        let proof = tree.proof(3).unwrap();
+        let check = |leaf: &[u8], proof: &[Sibling]| {
+            root_from_proof(HashAlgorithm::Sha256, leaf, 3, 6, proof).unwrap() == Some(tree.root())
+        };
+        assert!(check(&leaves[3], &proof));
+        assert!(!check(&leaves[4], &proof));
+
+        let mut flipped = proof.clone();
+        flipped[0] = match &flipped[0] {
+            Sibling::Left(hash) => Sibling::Right(hash.clone()),
+            Sibling::Right(hash) => Sibling::Left(hash.clone()),
+        };
+        assert!(!check(&leaves[3], &flipped));
+
+        let mut extra = proof.clone();
+        extra.push(Sibling::Right(hex::encode(&leaves[0])));
+        assert!(!check(&leaves[3], &extra));
+        assert!(!check(&leaves[3], &proof[..proof.len() - 1]));
+    }
+
+    #[test]
+    fn batch_proofs_round_trip_and_reject_tampering() {
+        let params = CommitParams {
+            scheme: CommitScheme::Hmac,
+            ..CommitParams::default()
+        };
+        let (commitment, secret, manifest) =
+            batch::commit_batch(&params, &PepperOptions::default(), &["alpha", "beta", "gamma"]).unwrap();
+        let proofs = manifest.proofs(&params, &secret).unwrap();
+        assert_eq!(proofs.len(), 3);
+        for proof in &proofs {
+            assert!(batch::verify_batch_proof(&commitment, proof).unwrap());
+        }
+
+        let tampered = |edit: fn(&mut BatchProof)| {
+            let mut proof = proofs[1].clone();
+            edit(&mut proof);
+            batch::verify_batch_proof(&commitment, &proof).unwrap_or(false)
+        };
+        assert!(!tampered(|proof| proof.input = hex::encode("delta")));
+        assert!(!tampered(|proof| proof.merkle.index = 0));
+        assert!(!tampered(|proof| proof.merkle.index = 2));
+        assert!(!tampered(|proof| proof.merkle.leaf_count = 2));
+        assert!(!tampered(|proof| proof.merkle.siblings.reverse()));
+        assert!(!tampered(|proof| proof.merkle.root_pepper = "00".repeat(32)));
+        assert!(!tampered(|proof| proof.merkle.format_version = 1));
+    }
+}
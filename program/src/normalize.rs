@@ -0,0 +1,74 @@
+//! Unicode normalization of inputs before they are hashed.
+//!
+//! The same visible text can be typed as different code points, e.g. "é" as one precomposed
+//! character or as "e" followed by a combining accent. Normalizing to NFC first makes both
+//! commit to the same hash. Inputs that aren't valid UTF-8 are hashed as they are.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
+use zeroize::Zeroizing;
+
+/// Normalization applied to the input when committing and verifying. Recorded with every saved secret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Normalization {
+    /// Unicode Normalization Form C, canonical composition
+    #[default]
+    #[serde(rename = "nfc")]
+    Nfc,
+    /// The bytes as typed, which every secret made before normalization existed uses
+    #[serde(rename = "none")]
+    Raw,
+}
+
+impl Normalization {
+    pub const ALL: [Self; 2] = [Self::Nfc, Self::Raw];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Nfc => "nfc",
+            Self::Raw => "none",
+        }
+    }
+
+    /// The normalization after this one in [`Normalization::ALL`], wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&normalization| normalization == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Default for records saved before normalization was recorded
+    pub(crate) fn legacy() -> Self {
+        Self::Raw
+    }
+
+    /// The bytes that get hashed for `input`
+    pub fn apply(self, input: &[u8]) -> Zeroizing<Vec<u8>> {
+        match (self, std::str::from_utf8(input)) {
+            (Self::Nfc, Ok(text)) => {
+                let mut normalized = Zeroizing::new(String::with_capacity(text.len()));
+                normalized.extend(text.nfc());
+                Zeroizing::new(normalized.as_bytes().to_vec())
+            }
+            _ => Zeroizing::new(input.to_vec()),
+        }
+    }
+}
+
+impl fmt::Display for Normalization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Normalization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|normalization| normalization.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown normalization {s:?}, expected nfc or none"))
+    }
+}
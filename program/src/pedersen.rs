@@ -0,0 +1,219 @@
+//! Pedersen commitments over Ristretto255, a homomorphic alternative to hash commitments.
+//!
+//! A value `v` is committed as `v·B + r·B_blinding` with a random blinding scalar `r`.
+//! Commitments add up: the sum of several commitments opens to the sum of their values
+//! with the sum of their blindings, so contributions can be aggregated and only the total
+//! revealed. The generators are the ones the bulletproofs crate uses, so range proofs made
+//! with it apply to these commitments too.
+
+use crate::{pepper, CommitError};
+use curve25519_dalek::constants::{RISTRETTO_BASEPOINT_COMPRESSED, RISTRETTO_BASEPOINT_POINT};
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{Identity, MultiscalarMul};
+use serde::{Deserialize, Serialize};
+use sha3::Sha3_512;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::Add;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// Random bytes reduced into a blinding scalar, twice its size so the result is uniform
+const BLINDING_SOURCE_LEN: usize = 64;
+
+/// The value generator, the Ristretto basepoint
+pub fn value_generator() -> RistrettoPoint {
+    RISTRETTO_BASEPOINT_POINT
+}
+
+/// The blinding generator: the basepoint's encoding hashed to the group with SHA3-512,
+/// so nobody knows its discrete log relative to the basepoint
+pub fn blinding_generator() -> RistrettoPoint {
+    RistrettoPoint::hash_from_bytes::<Sha3_512>(RISTRETTO_BASEPOINT_COMPRESSED.as_bytes())
+}
+
+#[derive(Debug)]
+pub enum PedersenError {
+    Hex(hex::FromHexError),
+    /// The bytes don't encode a point of the group
+    Point,
+    /// The bytes aren't a canonically encoded scalar
+    Scalar,
+    /// A stored value isn't 8 bytes long
+    Value,
+    /// The values of the opening are larger in total than 2^64 - 1
+    Overflow,
+}
+
+impl fmt::Display for PedersenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hex(e) => write!(f, "hex decoding failed: {e}"),
+            Self::Point => f.write_str("not a valid Ristretto255 point"),
+            Self::Scalar => f.write_str("not a canonical 32-byte scalar"),
+            Self::Value => f.write_str("a stored value must be 8 bytes"),
+            Self::Overflow => f.write_str("the values add up to more than 2^64 - 1"),
+        }
+    }
+}
+
+impl std::error::Error for PedersenError {}
+
+impl From<hex::FromHexError> for PedersenError {
+    fn from(e: hex::FromHexError) -> Self {
+        Self::Hex(e)
+    }
+}
+
+/// A published Pedersen commitment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PedersenCommitment(RistrettoPoint);
+
+impl PedersenCommitment {
+    pub fn point(&self) -> RistrettoPoint {
+        self.0
+    }
+
+    pub fn compress(&self) -> CompressedRistretto {
+        self.0.compress()
+    }
+
+    /// Parses the hex of a compressed point, e.g. one received from a counterparty
+    pub fn from_hex(commitment: &str) -> Result<Self, PedersenError> {
+        let bytes = hex::decode(commitment.trim())?;
+        let compressed = CompressedRistretto::from_slice(&bytes).map_err(|_| PedersenError::Point)?;
+        compressed.decompress().map(Self).ok_or(PedersenError::Point)
+    }
+}
+
+impl fmt::Display for PedersenCommitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.compress().as_bytes()))
+    }
+}
+
+impl Add for PedersenCommitment {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self(self.0 + other.0)
+    }
+}
+
+impl Sum for PedersenCommitment {
+    fn sum<I: Iterator<Item = Self>>(commitments: I) -> Self {
+        Self(commitments.map(|commitment| commitment.0).fold(RistrettoPoint::identity(), Add::add))
+    }
+}
+
+/// The value and blinding that open a commitment, wiped on drop
+#[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub struct Opening {
+    value: u64,
+    blinding: Scalar,
+}
+
+/// Never prints the value or the blinding, the secrets the commitment hides
+impl fmt::Debug for Opening {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Opening([REDACTED])")
+    }
+}
+
+impl Opening {
+    pub fn new(value: u64, blinding: Scalar) -> Self {
+        Self { value, blinding }
+    }
+
+    /// Parses a value and the hex of its canonical blinding scalar
+    pub fn from_hex(value: u64, blinding: &str) -> Result<Self, PedersenError> {
+        let bytes: [u8; 32] = hex::decode(blinding.trim())?
+            .try_into()
+            .map_err(|_| PedersenError::Scalar)?;
+        let blinding = Option::from(Scalar::from_canonical_bytes(bytes)).ok_or(PedersenError::Scalar)?;
+        Ok(Self::new(value, blinding))
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    pub fn blinding(&self) -> &Scalar {
+        &self.blinding
+    }
+
+    pub fn commitment(&self) -> PedersenCommitment {
+        PedersenCommitment(RistrettoPoint::multiscalar_mul(
+            [Scalar::from(self.value), self.blinding],
+            [value_generator(), blinding_generator()],
+        ))
+    }
+
+    /// The opening of the sum of the commitments `openings` open
+    pub fn aggregate<'a>(openings: impl IntoIterator<Item = &'a Opening>) -> Result<Self, PedersenError> {
+        let mut total = Self::new(0, Scalar::ZERO);
+        for opening in openings {
+            total.value = total.value.checked_add(opening.value).ok_or(PedersenError::Overflow)?;
+            total.blinding += opening.blinding;
+        }
+        Ok(total)
+    }
+}
+
+/// Commits to `value` with a blinding drawn from the health-tested OS RNG
+pub fn commit(value: u64) -> Result<(PedersenCommitment, Opening), CommitError> {
+    let source = Zeroizing::new(pepper::os_random(BLINDING_SOURCE_LEN)?);
+    let mut wide = Zeroizing::new([0u8; BLINDING_SOURCE_LEN]);
+    wide.copy_from_slice(&source);
+    let opening = Opening::new(value, Scalar::from_bytes_mod_order_wide(&wide));
+    Ok((opening.commitment(), opening))
+}
+
+/// Checks that `opening` opens `commitment`
+pub fn verify(commitment: &PedersenCommitment, opening: &Opening) -> bool {
+    opening.commitment() == *commitment
+}
+
+/// How a stored Pedersen commitment was made, recorded with its opening
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PedersenInfo {
+    /// Hex-encoded commitments summed into this one, empty for a single value
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openings_open_their_commitment() {
+        let (commitment, opening) = commit(42).unwrap();
+        assert!(verify(&commitment, &opening));
+        assert_eq!(PedersenCommitment::from_hex(&commitment.to_string()).unwrap(), commitment);
+
+        let parsed = Opening::from_hex(42, &hex::encode(opening.blinding().as_bytes())).unwrap();
+        assert!(verify(&commitment, &parsed));
+        assert!(!verify(&commitment, &Opening::new(43, *opening.blinding())));
+    }
+
+    #[test]
+    fn aggregates_open_the_sum() {
+        let parts: Vec<_> = [3, 5, 7].into_iter().map(|value| commit(value).unwrap()).collect();
+        let total = Opening::aggregate(parts.iter().map(|(_, opening)| opening)).unwrap();
+        assert_eq!(total.value(), 15);
+        assert_eq!(total.commitment(), parts.iter().map(|(commitment, _)| *commitment).sum());
+    }
+
+    #[test]
+    fn aggregates_refuse_to_overflow() {
+        let openings = [Opening::new(u64::MAX, Scalar::ONE), Opening::new(1, Scalar::ONE)];
+        assert!(matches!(Opening::aggregate(&openings), Err(PedersenError::Overflow)));
+    }
+
+    #[test]
+    fn debug_hides_the_opening() {
+        let opening = Opening::new(1234, Scalar::from(5678u64));
+        assert_eq!(format!("{opening:?}"), "Opening([REDACTED])");
+    }
+}
@@ -0,0 +1,108 @@
+//! Pepper generation.
+//!
+//! Peppers come from the OS RNG. Users who don't fully trust it can supply their own
+//! entropy, e.g. dice rolls or keyboard timings, which is mixed with the OS bytes through
+//! an HKDF-style extractor. Both inputs are kept as a [`PepperMix`] so an audit can
+//! rerun the extractor and confirm the pepper came from them.
+
+use crate::{health, CommitError, HashAlgorithm, PEPPER_LEN};
+use rand::rand_core::TryRngCore;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+pub const MIN_PEPPER_LEN: usize = 16;
+pub const MAX_PEPPER_LEN: usize = 64;
+
+/// Salt of the extractor, so its output can't be mistaken for any other HMAC in the client
+const EXTRACTOR_SALT: &[u8] = b"concoin-pepper-mix-v1";
+
+/// How new peppers are made
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PepperOptions {
+    /// Pepper length in bytes, between [`MIN_PEPPER_LEN`] and [`MAX_PEPPER_LEN`]
+    pub len: usize,
+    /// Extra randomness from the user, mixed with the OS bytes when set
+    pub user_entropy: Option<Zeroizing<Vec<u8>>>,
+}
+
+impl Default for PepperOptions {
+    fn default() -> Self {
+        Self {
+            len: PEPPER_LEN,
+            user_entropy: None,
+        }
+    }
+}
+
+/// Inputs of the extractor that produced a pepper, recorded next to the secret
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct PepperMix {
+    /// Hex-encoded bytes read from the OS RNG
+    pub os_random: String,
+    /// Hex-encoded user entropy
+    pub user_entropy: String,
+}
+
+/// Never prints the extractor inputs, which give away the pepper
+impl fmt::Debug for PepperMix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PepperMix([REDACTED])")
+    }
+}
+
+impl PepperMix {
+    /// Reruns the extractor, giving the pepper these inputs produce
+    pub fn extract(&self, len: usize) -> Result<Vec<u8>, hex::FromHexError> {
+        let os_random = Zeroizing::new(hex::decode(&self.os_random)?);
+        let user_entropy = Zeroizing::new(hex::decode(&self.user_entropy)?);
+        Ok(extract(&os_random, &user_entropy, len))
+    }
+}
+
+/// HKDF-Extract with SHA-512 over the length-prefixed OS bytes followed by the user entropy,
+/// truncated to `len`. The OS bytes alone keep the output uniform even if the user's input is not.
+pub fn extract(os_random: &[u8], user_entropy: &[u8], len: usize) -> Vec<u8> {
+    let os_len = (os_random.len() as u64).to_be_bytes();
+    let mut pepper = HashAlgorithm::Sha512.hmac(EXTRACTOR_SALT, &[&os_len, os_random, user_entropy]);
+    pepper.truncate(len);
+    pepper
+}
+
+/// Reads `len` bytes from the OS RNG that passed the health tests
+pub fn os_random(len: usize) -> Result<Vec<u8>, CommitError> {
+    health::startup()?;
+    let bytes = read_os_random(len)?;
+    health::check(&bytes)?;
+    Ok(bytes)
+}
+
+/// Reads `len` bytes from the OS RNG without testing them
+pub(crate) fn read_os_random(len: usize) -> Result<Vec<u8>, CommitError> {
+    let mut bytes = vec![0u8; len];
+    OsRng
+        .try_fill_bytes(&mut bytes)
+        .map_err(|e| CommitError::Rng(e.to_string()))?;
+    Ok(bytes)
+}
+
+/// Makes a pepper as `options` asks, along with the mix that produced it if user entropy was given
+pub fn generate(options: &PepperOptions) -> Result<(Vec<u8>, Option<PepperMix>), CommitError> {
+    if !(MIN_PEPPER_LEN..=MAX_PEPPER_LEN).contains(&options.len) {
+        return Err(CommitError::PepperLength(options.len));
+    }
+
+    let os_random = Zeroizing::new(os_random(options.len)?);
+    match &options.user_entropy {
+        None => Ok((os_random.to_vec(), None)),
+        Some(user_entropy) => {
+            let pepper = extract(&os_random, user_entropy, options.len);
+            let mix = PepperMix {
+                os_random: hex::encode(&*os_random),
+                user_entropy: hex::encode(&**user_entropy),
+            };
+            Ok((pepper, Some(mix)))
+        }
+    }
+}
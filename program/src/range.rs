@@ -0,0 +1,287 @@
+//! Zero-knowledge range proofs for Pedersen commitments.
+//!
+//! A Bulletproofs range proof shows that a committed value lies in `[min, max]` without
+//! revealing it. It proves `value - min` and `max - value` both fit in the smallest of 8,
+//! 16, 32 or 64 bits that spans the range, as one aggregated proof over commitments the
+//! verifier shifts from the published one. The bounds and the commitment's context are bound
+//! into the transcript, so a proof for one range, round or participant can't be passed off
+//! for another.
+
+use crate::pedersen::{self, Opening, PedersenCommitment, PedersenError};
+use crate::Context;
+use bulletproofs::{BulletproofGens, PedersenGens, ProofError};
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroizing;
+
+/// Version written to new bundles
+///
+/// 2: bundles carry the commitment's context, which the range proof is bound to
+pub const BUNDLE_FORMAT_VERSION: u32 = 2;
+
+/// Separates these proofs from any other protocol using the same generators
+const TRANSCRIPT_LABEL: &[u8] = b"concoin-range-proof-v1";
+
+/// Bit sizes a proof can be made for
+const BIT_SIZES: [usize; 4] = [8, 16, 32, 64];
+
+#[derive(Debug)]
+pub enum RangeError {
+    /// The lower bound is above the upper bound
+    Bounds { min: u64, max: u64 },
+    /// The committed value is outside the range, so no proof exists
+    OutOfRange { min: u64, max: u64 },
+    /// The bundle was written by a newer client
+    Format(u32),
+    Hex(hex::FromHexError),
+    Pedersen(PedersenError),
+    Proof(ProofError),
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bounds { min, max } => write!(f, "the range {min}..={max} is empty"),
+            Self::OutOfRange { min, max } => write!(f, "the committed value is outside {min}..={max}"),
+            Self::Format(version) => write!(f, "bundle format {version} is newer than this client supports"),
+            Self::Hex(e) => write!(f, "hex decoding failed: {e}"),
+            Self::Pedersen(e) => write!(f, "invalid commitment: {e}"),
+            Self::Proof(e) => write!(f, "range proof failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+impl From<hex::FromHexError> for RangeError {
+    fn from(e: hex::FromHexError) -> Self {
+        Self::Hex(e)
+    }
+}
+
+impl From<PedersenError> for RangeError {
+    fn from(e: PedersenError) -> Self {
+        Self::Pedersen(e)
+    }
+}
+
+impl From<ProofError> for RangeError {
+    fn from(e: ProofError) -> Self {
+        Self::Proof(e)
+    }
+}
+
+/// Proof that a committed value lies in `[min, max]`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RangeProof {
+    pub min: u64,
+    pub max: u64,
+    /// Hex-encoded aggregated Bulletproofs range proof
+    pub proof: String,
+}
+
+/// A commitment and the proofs attached to it, as handed to other participants
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitmentBundle {
+    pub format_version: u32,
+    /// Hex-encoded Pedersen commitment
+    pub commitment: String,
+    /// Round and participant the commitment was made for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<Context>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range_proof: Option<RangeProof>,
+}
+
+impl CommitmentBundle {
+    pub fn new(commitment: &PedersenCommitment, context: Option<Context>, range_proof: Option<RangeProof>) -> Self {
+        Self {
+            format_version: BUNDLE_FORMAT_VERSION,
+            commitment: commitment.to_string(),
+            context,
+            range_proof,
+        }
+    }
+
+    /// Checks the attached range proof against the commitment. True when none is attached.
+    pub fn verify(&self) -> Result<bool, RangeError> {
+        if self.format_version > BUNDLE_FORMAT_VERSION {
+            return Err(RangeError::Format(self.format_version));
+        }
+        let commitment = PedersenCommitment::from_hex(&self.commitment)?;
+        match &self.range_proof {
+            Some(range_proof) => verify(&commitment, self.context.as_ref(), range_proof),
+            None => Ok(true),
+        }
+    }
+}
+
+fn generators() -> PedersenGens {
+    PedersenGens {
+        B: pedersen::value_generator(),
+        B_blinding: pedersen::blinding_generator(),
+    }
+}
+
+/// Smallest supported bit size that holds `max - min`
+fn bits(min: u64, max: u64) -> Result<usize, RangeError> {
+    let span = max.checked_sub(min).ok_or(RangeError::Bounds { min, max })?;
+    Ok(BIT_SIZES
+        .into_iter()
+        .find(|&bits| bits == 64 || span < 1 << bits)
+        .expect("64 bits hold any span"))
+}
+
+fn transcript(context: Option<&Context>, min: u64, max: u64) -> Transcript {
+    let mut transcript = Transcript::new(TRANSCRIPT_LABEL);
+    // Merlin frames every message with its label, so a proof without a context can't
+    // stand in for one with a context either
+    if let Some(context) = context {
+        transcript.append_message(b"context", &context.encode());
+    }
+    transcript.append_u64(b"min", min);
+    transcript.append_u64(b"max", max);
+    transcript
+}
+
+/// Proves that the value `opening` opens lies in `[min, max]`, for the commitment made under `context`
+pub fn prove(opening: &Opening, context: Option<&Context>, min: u64, max: u64) -> Result<RangeProof, RangeError> {
+    let bits = bits(min, max)?;
+    let (Some(above_min), Some(below_max)) = (opening.value().checked_sub(min), max.checked_sub(opening.value()))
+    else {
+        return Err(RangeError::OutOfRange { min, max });
+    };
+
+    // Opens commitment - min·B and max·B - commitment, the ones the verifier derives
+    let blindings = Zeroizing::new([*opening.blinding(), -opening.blinding()]);
+    let (proof, _) = bulletproofs::RangeProof::prove_multiple(
+        &BulletproofGens::new(bits, 2),
+        &generators(),
+        &mut transcript(context, min, max),
+        &[above_min, below_max],
+        &*blindings,
+        bits,
+    )?;
+    Ok(RangeProof {
+        min,
+        max,
+        proof: hex::encode(proof.to_bytes()),
+    })
+}
+
+/// Checks that `range_proof` shows the value behind `commitment`, made under `context`, lies in its range
+pub fn verify(
+    commitment: &PedersenCommitment,
+    context: Option<&Context>,
+    range_proof: &RangeProof,
+) -> Result<bool, RangeError> {
+    let RangeProof { min, max, .. } = *range_proof;
+    let bits = bits(min, max)?;
+    let proof = bulletproofs::RangeProof::from_bytes(&hex::decode(&range_proof.proof)?)?;
+
+    let value_generator = pedersen::value_generator();
+    let shifted = [
+        (commitment.point() - Scalar::from(min) * value_generator).compress(),
+        (Scalar::from(max) * value_generator - commitment.point()).compress(),
+    ];
+    Ok(proof
+        .verify_multiple(
+            &BulletproofGens::new(bits, 2),
+            &generators(),
+            &mut transcript(context, min, max),
+            &shifted,
+            bits,
+        )
+        .is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round(round: &str) -> Context {
+        Context::new(round, "alice")
+    }
+
+    #[test]
+    fn values_in_range_prove_and_verify() {
+        let context = round("1");
+        for value in [10, 42, 100] {
+            let (commitment, opening) = pedersen::commit(value).unwrap();
+            let proof = prove(&opening, Some(&context), 10, 100).unwrap();
+            assert!(verify(&commitment, Some(&context), &proof).unwrap());
+        }
+
+        let (commitment, opening) = pedersen::commit(7).unwrap();
+        let proof = prove(&opening, None, 0, 7).unwrap();
+        assert!(verify(&commitment, None, &proof).unwrap());
+    }
+
+    #[test]
+    fn values_outside_the_range_have_no_proof() {
+        for value in [9, 101] {
+            let (_, opening) = pedersen::commit(value).unwrap();
+            assert!(matches!(
+                prove(&opening, None, 10, 100),
+                Err(RangeError::OutOfRange { min: 10, max: 100 })
+            ));
+        }
+    }
+
+    #[test]
+    fn proofs_are_bound_to_their_range_and_context() {
+        let context = round("1");
+        let (commitment, opening) = pedersen::commit(50).unwrap();
+        let proof = prove(&opening, Some(&context), 10, 100).unwrap();
+
+        let lower = RangeProof { min: 20, ..proof.clone() };
+        assert!(!verify(&commitment, Some(&context), &lower).unwrap());
+        let upper = RangeProof { max: 90, ..proof.clone() };
+        assert!(!verify(&commitment, Some(&context), &upper).unwrap());
+
+        assert!(!verify(&commitment, Some(&round("2")), &proof).unwrap());
+        assert!(!verify(&commitment, Some(&Context::new("1", "bob")), &proof).unwrap());
+        assert!(!verify(&commitment, None, &proof).unwrap());
+
+        let (other, _) = pedersen::commit(50).unwrap();
+        assert!(!verify(&other, Some(&context), &proof).unwrap());
+    }
+
+    #[test]
+    fn bit_sizes_cover_the_span() {
+        assert_eq!(bits(0, u64::MAX).unwrap(), 64);
+        assert_eq!(bits(0, 255).unwrap(), 8);
+        assert_eq!(bits(0, 256).unwrap(), 16);
+        assert_eq!(bits(u64::MAX, u64::MAX).unwrap(), 8);
+        assert!(matches!(bits(5, 4), Err(RangeError::Bounds { min: 5, max: 4 })));
+    }
+
+    #[test]
+    fn the_full_range_proves() {
+        let (commitment, opening) = pedersen::commit(u64::MAX).unwrap();
+        let proof = prove(&opening, None, 0, u64::MAX).unwrap();
+        assert!(verify(&commitment, None, &proof).unwrap());
+    }
+
+    #[test]
+    fn bundles_check_their_proof() {
+        let context = round("1");
+        let (commitment, opening) = pedersen::commit(50).unwrap();
+        let proof = prove(&opening, Some(&context), 10, 100).unwrap();
+        let bundle = CommitmentBundle::new(&commitment, Some(context), Some(proof));
+        assert!(bundle.verify().unwrap());
+
+        let replayed = CommitmentBundle {
+            context: Some(round("2")),
+            ..bundle.clone()
+        };
+        assert!(!replayed.verify().unwrap());
+        let newer = CommitmentBundle {
+            format_version: BUNDLE_FORMAT_VERSION + 1,
+            ..bundle
+        };
+        assert!(matches!(newer.verify(), Err(RangeError::Format(_))));
+    }
+}
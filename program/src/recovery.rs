@@ -0,0 +1,120 @@
+//! Emergency save of secrets that have not reached the secrets file yet.
+//!
+//! Every commitment made in the TUI is tracked here until it is saved. If the client
+//! panics or is killed by a signal, the terminal is restored and the tracked secrets
+//! are written next to the secrets file, where the next session offers to import them.
+
+use program::store::{self, SecretRecord};
+use std::io;
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use zeroize::Zeroizing;
+
+struct Pending {
+    /// Recovery file to write to, set once the hooks are installed
+    path: Option<String>,
+    /// Passphrase of the secrets file, reused so the recovery file is not left in plaintext
+    passphrase: Option<Zeroizing<String>>,
+    secrets: Vec<SecretRecord>,
+}
+
+static PENDING: Mutex<Pending> = Mutex::new(Pending {
+    path: None,
+    passphrase: None,
+    secrets: Vec::new(),
+});
+
+/// A panic elsewhere must not stop the emergency save, so ignore poisoning
+fn pending() -> MutexGuard<'static, Pending> {
+    PENDING.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn recovery_path(secrets_path: &str) -> String {
+    format!("{secrets_path}.recovery")
+}
+
+/// Installs the panic hook and signal handler. Call before `ratatui::init`, whose own panic
+/// hook then runs first and hands over to this one.
+pub fn install(secrets_path: &str) -> io::Result<()> {
+    pending().path = Some(recovery_path(secrets_path));
+
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        ratatui::restore();
+        write_pending();
+        previous_hook(info);
+    }));
+
+    #[cfg(unix)]
+    {
+        use signal_hook::consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM};
+        use signal_hook::iterator::Signals;
+
+        let mut signals = Signals::new([SIGHUP, SIGINT, SIGQUIT, SIGTERM])?;
+        std::thread::spawn(move || {
+            if let Some(signal) = signals.forever().next() {
+                ratatui::restore();
+                write_pending();
+                std::process::exit(128 + signal);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Remembers a secret until the next successful save
+pub fn track(record: SecretRecord) {
+    pending().secrets.push(record);
+}
+
+pub fn set_passphrase(passphrase: Option<Zeroizing<String>>) {
+    pending().passphrase = passphrase;
+}
+
+/// Forgets the tracked secrets once they are in the secrets file
+pub fn saved() {
+    pending().secrets.clear();
+}
+
+/// Writes tracked secrets to the recovery file. Falls back to printing them,
+/// since the terminal has been restored by then and this is the last chance to keep them.
+fn write_pending() {
+    // The panicking thread may hold the lock itself, so don't wait for it
+    let pending = match PENDING.try_lock() {
+        Ok(pending) => pending,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        Err(TryLockError::WouldBlock) => {
+            eprintln!("Could not write unsaved secrets: they were being updated when the client stopped");
+            return;
+        }
+    };
+    let Some(path) = &pending.path else {
+        return;
+    };
+    if pending.secrets.is_empty() {
+        return;
+    }
+
+    match store::merge_data_into_json(&pending.secrets, path, pending.passphrase.as_deref().map(String::as_str)) {
+        Ok(_) => eprintln!("Unsaved secrets were written to {path}"),
+        Err(e) => {
+            eprintln!("Could not write unsaved secrets to {path}: {e}");
+            eprintln!("Pepper+input hex of every unsaved secret:");
+            for record in &pending.secrets {
+                eprintln!("{} {}", record.commitment, record.secret_hex().as_str());
+            }
+        }
+    }
+}
+
+/// Reads a recovery file left by an earlier session, if there is one
+pub fn load(secrets_path: &str, passphrase: Option<&str>) -> io::Result<Vec<SecretRecord>> {
+    store::load_data_from_json(recovery_path(secrets_path), passphrase)
+}
+
+pub fn remove(secrets_path: &str) -> io::Result<()> {
+    match std::fs::remove_file(recovery_path(secrets_path)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,251 @@
+//! Peppers derived from a master seed.
+//!
+//! Instead of fresh OS randomness, a pepper can be derived with HKDF-SHA512 from a master
+//! seed, a counter and a label. The seed is shown once as a 24-word BIP-39 mnemonic; with it
+//! and the input, the pepper of any past commitment can be regenerated after the secrets
+//! file is lost. The seed and the next unused counter are kept in `<secrets file>.seed`,
+//! encrypted with the same passphrase as the secrets file.
+
+use crate::encryption;
+use crate::memory::SecretBytes;
+use crate::store::{self, SaveError};
+use crate::{pepper, CommitError, CommitParams, Commitment, HashAlgorithm, Secret};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::Path;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// Bytes of seed, which a 24-word mnemonic encodes
+pub const SEED_LEN: usize = 32;
+
+/// Version written to new seed files
+pub const SEED_FORMAT_VERSION: u32 = 1;
+
+/// HKDF salt, so keys derived here can't be mistaken for any other HMAC in the client
+const HKDF_SALT: &[u8] = b"concoin-master-seed-v1";
+const INFO_PEPPER: &[u8] = b"pepper";
+const INFO_SEED_ID: &[u8] = b"seed-id";
+
+/// Where a derived pepper came from, recorded with the secret
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Derivation {
+    /// Identifies the seed without revealing it
+    pub seed_id: String,
+    pub counter: u64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub label: String,
+}
+
+#[derive(Debug)]
+pub enum SeedError {
+    /// The words are not a valid BIP-39 mnemonic
+    Mnemonic(bip39::Error),
+    /// The mnemonic is valid but encodes a seed of the wrong size
+    Length(usize),
+    /// A seed file already exists and would be overwritten
+    Exists,
+    /// No seed file has been created for these secrets
+    Missing,
+    Read(io::Error),
+    Save(SaveError),
+}
+
+impl fmt::Display for SeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mnemonic(e) => write!(f, "invalid mnemonic: {e}"),
+            Self::Length(len) => write!(f, "mnemonic holds {len} bytes, expected a {SEED_LEN}-byte seed"),
+            Self::Exists => f.write_str("a master seed already exists for this secrets file"),
+            Self::Missing => f.write_str("no master seed exists for this secrets file"),
+            Self::Read(e) => write!(f, "could not read the seed file: {e}"),
+            Self::Save(e) => write!(f, "could not save the seed file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SeedError {}
+
+impl From<SaveError> for SeedError {
+    fn from(e: SaveError) -> Self {
+        Self::Save(e)
+    }
+}
+
+pub struct MasterSeed {
+    bytes: SecretBytes,
+}
+
+impl MasterSeed {
+    /// Draws a new seed from the health-tested OS RNG
+    pub fn generate() -> Result<Self, CommitError> {
+        Ok(Self {
+            bytes: SecretBytes::new(pepper::os_random(SEED_LEN)?),
+        })
+    }
+
+    pub fn from_mnemonic(words: &str) -> Result<Self, SeedError> {
+        let mnemonic = bip39::Mnemonic::parse_normalized(words.trim()).map_err(SeedError::Mnemonic)?;
+        let bytes = SecretBytes::new(mnemonic.to_entropy());
+        if bytes.len() != SEED_LEN {
+            return Err(SeedError::Length(bytes.len()));
+        }
+        Ok(Self { bytes })
+    }
+
+    pub fn mnemonic(&self) -> Zeroizing<String> {
+        let mnemonic = bip39::Mnemonic::from_entropy(&self.bytes)
+            .expect("a 32-byte seed is a valid mnemonic length");
+        Zeroizing::new(mnemonic.to_string())
+    }
+
+    /// HKDF-Expand of `info` into one SHA-512 block, enough for any pepper
+    fn expand(&self, info: &[&[u8]]) -> Vec<u8> {
+        let prk = Zeroizing::new(HashAlgorithm::Sha512.hmac(HKDF_SALT, &[&self.bytes]));
+        let mut parts = info.to_vec();
+        parts.push(&[1]);
+        HashAlgorithm::Sha512.hmac(&prk, &parts)
+    }
+
+    /// Short public name of the seed, stored with every pepper derived from it
+    pub fn id(&self) -> String {
+        hex::encode(&self.expand(&[INFO_SEED_ID])[..8])
+    }
+
+    pub fn derive_pepper(&self, counter: u64, label: &str, len: usize) -> Vec<u8> {
+        let mut pepper = self.expand(&[INFO_PEPPER, &counter.to_be_bytes(), label.as_bytes()]);
+        pepper.truncate(len);
+        pepper
+    }
+
+    pub fn derivation(&self, counter: u64, label: &str) -> Derivation {
+        Derivation {
+            seed_id: self.id(),
+            counter,
+            label: label.to_string(),
+        }
+    }
+
+    /// Regenerates the secret behind `commitment` by trying every counter up to `max_counter`
+    pub fn restore(
+        &self,
+        commitment: &Commitment,
+        input: &[u8],
+        label: &str,
+        pepper_len: usize,
+        max_counter: u64,
+    ) -> Option<Secret> {
+        let input = commitment.params().normalization.apply(input);
+        (0..=max_counter).find_map(|counter| {
+            let secret = self.secret(counter, label, pepper_len, &input);
+            crate::verify(commitment, &secret).then_some(secret)
+        })
+    }
+
+    fn secret(&self, counter: u64, label: &str, pepper_len: usize, input: &[u8]) -> Secret {
+        Secret::new(self.derive_pepper(counter, label, pepper_len), input.to_vec())
+            .with_derivation(Some(self.derivation(counter, label)))
+    }
+}
+
+/// Commits to `input` with the pepper `seed` derives for `counter` and `label`
+pub fn commit_derived(
+    params: &CommitParams,
+    seed: &MasterSeed,
+    counter: u64,
+    label: &str,
+    pepper_len: usize,
+    input: impl AsRef<[u8]>,
+) -> Result<(Commitment, Secret), CommitError> {
+    params.check_pepper_len(pepper_len)?;
+    let secret = seed.secret(counter, label, pepper_len, &params.normalization.apply(input.as_ref()));
+    Ok((secret.commitment(params), secret))
+}
+
+/// On-disk layout of the seed file
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct SeedFile {
+    format_version: u32,
+    seed: String,
+    next_counter: u64,
+}
+
+pub fn seed_path(secrets_path: &str) -> String {
+    format!("{secrets_path}.seed")
+}
+
+fn read(path: &Path, passphrase: Option<&str>) -> Result<Option<SeedFile>, SeedError> {
+    let Some(json) = store::read_json(path, passphrase).map_err(SeedError::Read)? else {
+        return Ok(None);
+    };
+    let file: SeedFile = serde_json::from_slice(&json).map_err(|e| SeedError::Read(e.into()))?;
+    if file.format_version > SEED_FORMAT_VERSION {
+        return Err(SeedError::Read(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Seed file format {} is newer than this client supports", file.format_version),
+        )));
+    }
+    Ok(Some(file))
+}
+
+fn write(path: &Path, file: &SeedFile, passphrase: Option<&str>) -> Result<(), SeedError> {
+    let mut json_string = Zeroizing::new(serde_json::to_string_pretty(file).map_err(SaveError::Serialize)?);
+    if let Some(passphrase) = passphrase {
+        let container =
+            encryption::encrypt(json_string.as_bytes(), passphrase).map_err(SaveError::Encrypt)?;
+        json_string = Zeroizing::new(serde_json::to_string_pretty(&container).map_err(SaveError::Serialize)?);
+    }
+    Ok(store::write_atomic(path, json_string.as_bytes())?)
+}
+
+fn decode(file: &SeedFile) -> Result<MasterSeed, SeedError> {
+    let bytes = hex::decode(&file.seed)
+        .map_err(|e| SeedError::Read(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+    Ok(MasterSeed {
+        bytes: SecretBytes::new(bytes),
+    })
+}
+
+pub fn exists(secrets_path: &str) -> bool {
+    Path::new(&seed_path(secrets_path)).exists()
+}
+
+/// Stores a new seed for `secrets_path`, refusing to replace an existing one
+pub fn save_new(secrets_path: &str, seed: &MasterSeed, passphrase: Option<&str>) -> Result<(), SeedError> {
+    let _lock = store::lock(secrets_path)?;
+    let path = seed_path(secrets_path);
+    if Path::new(&path).exists() {
+        return Err(SeedError::Exists);
+    }
+    let file = SeedFile {
+        format_version: SEED_FORMAT_VERSION,
+        seed: hex::encode(&*seed.bytes),
+        next_counter: 0,
+    };
+    write(path.as_ref(), &file, passphrase)
+}
+
+/// Loads the seed and reserves the next counter, so no two commitments share a pepper
+pub fn next_counter(secrets_path: &str, passphrase: Option<&str>) -> Result<(MasterSeed, u64), SeedError> {
+    let _lock = store::lock(secrets_path)?;
+    let path = seed_path(secrets_path);
+    let mut file = read(path.as_ref(), passphrase)?.ok_or(SeedError::Missing)?;
+    let counter = file.next_counter;
+    file.next_counter += 1;
+    write(path.as_ref(), &file, passphrase)?;
+    Ok((decode(&file)?, counter))
+}
+
+/// Rewrites the seed file under a new passphrase, e.g. after the secrets file was saved with one
+pub fn reencrypt(
+    secrets_path: &str,
+    old_passphrase: Option<&str>,
+    new_passphrase: Option<&str>,
+) -> Result<(), SeedError> {
+    let _lock = store::lock(secrets_path)?;
+    let path = seed_path(secrets_path);
+    match read(path.as_ref(), old_passphrase)? {
+        Some(file) => write(path.as_ref(), &file, new_passphrase),
+        None => Ok(()),
+    }
+}
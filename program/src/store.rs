@@ -0,0 +1,569 @@
+//! Reading and writing the secrets file.
+//!
+//! The file is a versioned JSON document holding one [`SecretRecord`] per commitment.
+//! Files written by older clients, a bare `[["<pepper hex><input hex>"]]` list,
+//! are detected on load and upgraded in memory; the next save writes the current format.
+//!
+//! When a passphrase is given the whole document is wrapped in an [`EncryptedContainer`].
+//! Saves are atomic, owner-only and serialized between instances by an advisory lock.
+
+use crate::encryption::{self, EncryptedContainer, EncryptionError};
+use crate::memory::SecretString;
+use crate::pedersen::PedersenError;
+use crate::{
+    BatchManifest, ChainPosition, CommitError, CommitParams, CommitScheme, Commitment, Context, Derivation, FileInput, HashAlgorithm,
+    HashChain, Normalization, Opening, PedersenInfo, PepperMix, Secret, TreeManifest,
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, ErrorKind, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+/// File the client reads and writes secrets to unless told otherwise
+pub const DEFAULT_PATH: &str = "secrets.json";
+
+/// Version written to new files. Bump it whenever the schema changes shape.
+///
+/// 2: secrets may carry a domain separation context
+/// 3: secrets record their commitment scheme
+/// 4: peppers may be 16 to 64 bytes and record the entropy mixed into them
+/// 5: peppers may be derived from a master seed
+/// 6: secrets record the Unicode normalization applied to the input
+/// 7: the input may be a file, recorded by path, size and content hash
+/// 8: the input may be the Merkle root of a directory, recorded with its manifest
+/// 9: the input may be the Merkle root of a batch of inputs, recorded with them
+/// 10: the pepper may be the seed of a hash chain, recorded with how far it has been revealed
+/// 11: secrets may open Pedersen commitments, with the blinding as pepper and the value as input
+pub const FORMAT_VERSION: u32 = 11;
+
+/// On-disk layout of the secrets file
+#[derive(Serialize, Deserialize)]
+struct SecretsFile<'a> {
+    format_version: u32,
+    secrets: Cow<'a, [SecretRecord]>,
+}
+
+/// Just the version of a secrets file, read before the rest so a newer layout is reported as such
+#[derive(Deserialize)]
+struct FormatVersion {
+    format_version: Option<u64>,
+}
+
+/// Everything needed to reveal and re-check one commitment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretRecord {
+    pub hash_algorithm: HashAlgorithm,
+    /// Missing in files older than version 3, which only had the concat scheme
+    #[serde(default)]
+    pub scheme: CommitScheme,
+    /// Missing in files older than version 6, whose inputs were hashed as typed
+    #[serde(default = "Normalization::legacy")]
+    pub normalization: Normalization,
+    /// Hex-encoded pepper, wiped on drop
+    pub pepper: SecretString,
+    /// Hex-encoded input, wiped on drop
+    pub input: SecretString,
+    /// Hex-encoded commitment hash
+    pub commitment: String,
+    /// Seconds since the Unix epoch, unknown for secrets migrated from the legacy format
+    pub created_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Round the commitment is bound to, hashed ahead of the pepper
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<Context>,
+    /// OS and user entropy the pepper was extracted from, for audits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pepper_mix: Option<PepperMix>,
+    /// Master seed, counter and label the pepper was derived from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivation: Option<Derivation>,
+    /// File committed to, in which case the input is empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<FileInput>,
+    /// Directory committed to, in which case the input is its Merkle root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree: Option<TreeManifest>,
+    /// Batch of inputs committed to, in which case the input is their Merkle root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch: Option<BatchManifest>,
+    /// Hash chain the pepper is the seed of, in which case the commitment is its tip
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain: Option<ChainPosition>,
+    /// Set for a Pedersen commitment, whose hash algorithm and scheme don't apply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pedersen: Option<PedersenInfo>,
+}
+
+impl SecretRecord {
+    pub fn new(commitment: &Commitment, secret: &Secret, label: Option<String>) -> Self {
+        Self {
+            hash_algorithm: commitment.algorithm(),
+            scheme: commitment.params().scheme,
+            normalization: commitment.params().normalization,
+            context: commitment.params().context.clone(),
+            pepper: SecretString::new(hex::encode(secret.pepper())),
+            input: SecretString::new(hex::encode(secret.input())),
+            commitment: commitment.to_string(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|elapsed| elapsed.as_secs()),
+            label,
+            pepper_mix: secret.mix().cloned(),
+            derivation: secret.derivation().cloned(),
+            file: None,
+            tree: None,
+            batch: None,
+            chain: None,
+            pedersen: None,
+        }
+    }
+
+    /// Records the opening of a Pedersen commitment: the blinding scalar is kept as the
+    /// pepper and the big-endian value as the input. `context` is the round the commitment
+    /// belongs to, which its range proofs are bound to.
+    pub fn from_pedersen(
+        opening: &Opening,
+        info: PedersenInfo,
+        context: Option<Context>,
+        label: Option<String>,
+    ) -> Self {
+        let blinding = Zeroizing::new(opening.blinding().to_bytes());
+        let secret = Secret::new(blinding.to_vec(), opening.value().to_be_bytes().to_vec());
+        let params = CommitParams {
+            context,
+            normalization: Normalization::Raw,
+            ..CommitParams::default()
+        };
+        let commitment = Commitment::from_hex(params, &opening.commitment().to_string());
+        let mut record = Self::new(&commitment, &secret, label);
+        record.pedersen = Some(info);
+        record
+    }
+
+    /// Records `chain`, whose tip this record must have been made with
+    pub fn from_chain(chain: &HashChain, label: Option<String>) -> Self {
+        let mut record = Self::new(&chain.tip(), chain.secret(), label);
+        record.chain = Some(chain.position());
+        record
+    }
+
+    pub fn with_file(mut self, file: Option<FileInput>) -> Self {
+        self.file = file;
+        self
+    }
+
+    pub fn with_tree(mut self, tree: Option<TreeManifest>) -> Self {
+        self.tree = tree;
+        self
+    }
+
+    pub fn with_batch(mut self, batch: Option<BatchManifest>) -> Self {
+        self.batch = batch;
+        self
+    }
+
+    /// Decodes the secret. Its bytes are wiped and kept out of swap from then on.
+    pub fn secret(&self) -> Result<Secret, hex::FromHexError> {
+        let secret = Secret::new(hex::decode(&*self.pepper)?, hex::decode(&*self.input)?);
+        Ok(secret
+            .with_mix(self.pepper_mix.clone())
+            .with_derivation(self.derivation.clone()))
+    }
+
+    /// Whether rerunning the extractor on the recorded mix gives the stored pepper.
+    /// True when no mix was recorded.
+    pub fn mix_matches(&self) -> bool {
+        let Some(mix) = &self.pepper_mix else {
+            return true;
+        };
+        let pepper = hex::decode(&*self.pepper).map(Zeroizing::new);
+        match (mix.extract(self.pepper.len() / 2).map(Zeroizing::new), pepper) {
+            (Ok(extracted), Ok(pepper)) => extracted == pepper,
+            _ => false,
+        }
+    }
+
+    /// The hash chain this record's pepper seeds, if it is one. Fails if its position is invalid.
+    pub fn hash_chain(&self) -> Result<Option<HashChain>, CommitError> {
+        let Some(position) = self.chain else {
+            return Ok(None);
+        };
+        HashChain::new(self.secret()?, position).map(Some)
+    }
+
+    /// The opening of this record's Pedersen commitment, if it is one
+    pub fn pedersen_opening(&self) -> Result<Option<Opening>, PedersenError> {
+        if self.pedersen.is_none() {
+            return Ok(None);
+        }
+        let value: [u8; 8] = hex::decode(&*self.input)?
+            .try_into()
+            .map_err(|_| PedersenError::Value)?;
+        Opening::from_hex(u64::from_be_bytes(value), &self.pepper).map(Some)
+    }
+
+    /// Takes on `other`'s chain position if it is further along. Reveals never go back,
+    /// so when two copies of a chain meet the one that revealed more wins.
+    pub fn advance_chain(&mut self, other: &SecretRecord) {
+        if let (Some(position), Some(other)) = (&mut self.chain, other.chain) {
+            position.revealed = position.revealed.max(other.revealed);
+        }
+    }
+
+    pub fn params(&self) -> CommitParams {
+        CommitParams {
+            algorithm: self.hash_algorithm,
+            scheme: self.scheme,
+            context: self.context.clone(),
+            normalization: self.normalization,
+        }
+    }
+
+    pub fn commitment(&self) -> Commitment {
+        Commitment::from_hex(self.params(), &self.commitment)
+    }
+
+    /// Pepper hex followed by input hex, the form handed to a verifier
+    pub fn secret_hex(&self) -> Zeroizing<String> {
+        let mut combined = Zeroizing::new(String::with_capacity(self.pepper.len() + self.input.len()));
+        combined.push_str(&self.pepper);
+        combined.push_str(&self.input);
+        combined
+    }
+
+    /// Upgrades one entry of the legacy format, recomputing its commitment.
+    /// Legacy secrets were always committed with SHA-512.
+    fn from_legacy(combined: &str) -> io::Result<Self> {
+        let secret = Secret::from_hex(combined).map_err(|e| {
+            io::Error::new(ErrorKind::InvalidData, format!("Invalid legacy secret: {e}"))
+        })?;
+        let params = CommitParams {
+            algorithm: HashAlgorithm::Sha512,
+            scheme: CommitScheme::Concat,
+            context: None,
+            normalization: Normalization::Raw,
+        };
+        let mut record = Self::new(&secret.commitment(&params), &secret, None);
+        record.created_at = None;
+        Ok(record)
+    }
+}
+
+/// Reads the file's JSON, decrypting it first if it is encrypted. Returns None if it doesn't exist.
+/// Callers deserialize straight from the returned bytes, which are wiped on drop, rather than
+/// through a `serde_json::Value` that would keep unwiped copies of every string.
+pub(crate) fn read_json(filename: &Path, passphrase: Option<&str>) -> io::Result<Option<Zeroizing<Vec<u8>>>> {
+    let json = match std::fs::read(filename) {
+        Ok(json) => Zeroizing::new(json),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    if !EncryptedContainer::detect(&json) {
+        return Ok(Some(json));
+    }
+
+    let Some(passphrase) = passphrase else {
+        return Err(io::Error::new(
+            ErrorKind::PermissionDenied,
+            "Secrets file is encrypted and no passphrase was given",
+        ));
+    };
+    let container: EncryptedContainer = serde_json::from_slice(&json)?;
+    let plaintext = encryption::decrypt(&container, passphrase)
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+    Ok(Some(plaintext))
+}
+
+/// Makes a created or renamed directory entry durable
+#[cfg(unix)]
+pub fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    std::fs::File::open(parent)?.sync_all()
+}
+
+/// Directories can't be opened for syncing here, so there is nothing to do
+#[cfg(not(unix))]
+pub fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Checks whether the file exists and is encrypted, so a caller knows to ask for a passphrase
+pub fn is_encrypted(filename: impl AsRef<Path>) -> io::Result<bool> {
+    match std::fs::read(filename) {
+        Ok(json) => Ok(EncryptedContainer::detect(&Zeroizing::new(json))),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Loads every stored secret, treating a missing file as empty
+pub fn load_data_from_json(
+    filename: impl AsRef<Path>,
+    passphrase: Option<&str>,
+) -> io::Result<Vec<SecretRecord>> {
+    let Some(json) = read_json(filename.as_ref(), passphrase)? else {
+        return Ok(Vec::new());
+    };
+
+    if json.trim_ascii_start().starts_with(b"[") {
+        // Legacy layout: a list of single-element lists of pepper+input hex
+        let legacy: Vec<Vec<SecretString>> = serde_json::from_slice(&json)?;
+        return legacy
+            .iter()
+            .flatten()
+            .map(|combined| SecretRecord::from_legacy(combined))
+            .collect();
+    }
+
+    let FormatVersion { format_version } = serde_json::from_slice(&json)?;
+    match format_version {
+        Some(version) if version <= u64::from(FORMAT_VERSION) => {}
+        Some(version) => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Secrets file format {version} is newer than this client supports"),
+            ));
+        }
+        None => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Secrets file has no format_version",
+            ));
+        }
+    }
+
+    let file: SecretsFile = serde_json::from_slice(&json)?;
+    Ok(file.secrets.into_owned())
+}
+
+/// Ways saving the secrets file can fail, kept apart so the user can tell what went wrong
+#[derive(Debug)]
+pub enum SaveError {
+    /// The existing file could not be read for merging
+    Load(io::Error),
+    Serialize(serde_json::Error),
+    Encrypt(EncryptionError),
+    /// The lock file could not be opened or locked
+    Lock(io::Error),
+    /// Another client instance held the lock for too long
+    Locked,
+    CreateTemp(io::Error),
+    Write(io::Error),
+    Permissions(io::Error),
+    Sync(io::Error),
+    Rename(io::Error),
+    /// The secrets file was saved but the compacted journal could not be removed
+    RemoveJournal(io::Error),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Load(e) => write!(f, "could not read the existing file to merge into: {e}"),
+            Self::Serialize(e) => write!(f, "could not serialize the secrets: {e}"),
+            Self::Encrypt(e) => write!(f, "could not encrypt the secrets: {e}"),
+            Self::Lock(e) => write!(f, "could not lock the secrets file: {e}"),
+            Self::Locked => f.write_str("another client instance is saving the secrets file"),
+            Self::CreateTemp(e) => write!(f, "could not create the temporary file: {e}"),
+            Self::Write(e) => write!(f, "could not write the temporary file: {e}"),
+            Self::Permissions(e) => write!(f, "could not restrict the file to its owner: {e}"),
+            Self::Sync(e) => write!(f, "could not flush the file to disk: {e}"),
+            Self::Rename(e) => write!(f, "could not replace the secrets file: {e}"),
+            Self::RemoveJournal(e) => write!(f, "saved, but could not remove the journal: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+/// Advisory lock on `<file>.lock`, released when dropped. The secrets file itself
+/// can't carry the lock because every save replaces it with a new file.
+pub struct FileLock {
+    _file: File,
+}
+
+/// How long to wait for another instance to finish saving
+const LOCK_ATTEMPTS: u32 = 40;
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+pub fn lock(filename: impl AsRef<Path>) -> Result<FileLock, SaveError> {
+    let mut lock_path = filename.as_ref().as_os_str().to_owned();
+    lock_path.push(".lock");
+    let file = owner_only_options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)
+        .map_err(SaveError::Lock)?;
+
+    for _ in 0..LOCK_ATTEMPTS {
+        match file.try_lock() {
+            Ok(()) => return Ok(FileLock { _file: file }),
+            Err(TryLockError::WouldBlock) => std::thread::sleep(LOCK_RETRY_DELAY),
+            Err(TryLockError::Error(e)) => return Err(SaveError::Lock(e)),
+        }
+    }
+    Err(SaveError::Locked)
+}
+
+/// Options for creating a file only its owner can read or write
+pub fn owner_only_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    options.mode(0o600);
+    options
+}
+
+#[cfg(unix)]
+fn restrict_to_owner(file: &File) -> io::Result<()> {
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn restrict_to_owner(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+/// Writes the secrets, encrypted if a passphrase is given.
+///
+/// The data goes to a temporary file that is flushed and then renamed over the target,
+/// so an interrupted save leaves the previous file intact. Hold a [`lock`] around it
+/// when other instances may be writing; [`merge_data_into_json`] does.
+pub fn save_data_to_json(
+    data: &[SecretRecord],
+    filename: impl AsRef<Path>,
+    passphrase: Option<&str>,
+) -> Result<(), SaveError> {
+    let filename = filename.as_ref();
+    let file = SecretsFile {
+        format_version: FORMAT_VERSION,
+        secrets: Cow::Borrowed(data),
+    };
+
+    // Convert the struct to a pretty JSON string
+    let mut json_string = Zeroizing::new(serde_json::to_string_pretty(&file).map_err(SaveError::Serialize)?);
+    if let Some(passphrase) = passphrase {
+        let container =
+            encryption::encrypt(json_string.as_bytes(), passphrase).map_err(SaveError::Encrypt)?;
+        json_string = Zeroizing::new(serde_json::to_string_pretty(&container).map_err(SaveError::Serialize)?);
+    }
+    write_atomic(filename, json_string.as_bytes())
+}
+
+/// Replaces `filename` with `contents` through a flushed temporary file, owner-only
+pub(crate) fn write_atomic(filename: &Path, contents: &[u8]) -> Result<(), SaveError> {
+    let mut temp_path = filename.as_os_str().to_owned();
+    temp_path.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = PathBuf::from(temp_path);
+
+    let result = write_synced(&temp_path, contents).and_then(|()| {
+        std::fs::rename(&temp_path, filename).map_err(SaveError::Rename)?;
+        sync_parent_dir(filename).map_err(SaveError::Sync)
+    });
+    if result.is_err() {
+        // Don't leave a partial copy of the secrets lying around
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn write_synced(path: &Path, contents: &[u8]) -> Result<(), SaveError> {
+    let mut file = owner_only_options()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(path)
+        .map_err(SaveError::CreateTemp)?;
+    // The mode above only applies to new files
+    restrict_to_owner(&file).map_err(SaveError::Permissions)?;
+    file.write_all(contents).map_err(SaveError::Write)?;
+    file.sync_all().map_err(SaveError::Sync)
+}
+
+/// Adds `data` to whatever is already in the file, skipping commitments it already holds
+/// apart from moving hash chains forward, and returns the combined list that was written
+pub fn merge_data_into_json(
+    data: &[SecretRecord],
+    filename: impl AsRef<Path>,
+    passphrase: Option<&str>,
+) -> Result<Vec<SecretRecord>, SaveError> {
+    let _lock = lock(&filename)?;
+    merge_locked(data, filename.as_ref(), passphrase, passphrase)
+}
+
+/// [`merge_data_into_json`] for callers that already hold the lock, reading the file with
+/// `passphrase` and writing it with `new_passphrase`. Each new record is copied once, into
+/// the returned list.
+pub(crate) fn merge_locked<'a>(
+    data: impl IntoIterator<Item = &'a SecretRecord>,
+    filename: &Path,
+    passphrase: Option<&str>,
+    new_passphrase: Option<&str>,
+) -> Result<Vec<SecretRecord>, SaveError> {
+    let mut merged = load_data_from_json(filename, passphrase).map_err(SaveError::Load)?;
+    for record in data {
+        match merged.iter_mut().find(|existing| existing.commitment == record.commitment) {
+            Some(existing) => existing.advance_chain(record),
+            None => merged.push(record.clone()),
+        }
+    }
+
+    save_data_to_json(&merged, filename, new_passphrase)?;
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pepper 00 01 .. 1f followed by "e" and a combining acute accent, as a legacy client wrote it
+    const LEGACY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f65cc81";
+
+    #[test]
+    fn legacy_entries_upgrade_as_raw_sha512_concat() {
+        let record = SecretRecord::from_legacy(LEGACY).unwrap();
+        let raw_sha512 = CommitParams {
+            algorithm: HashAlgorithm::Sha512,
+            scheme: CommitScheme::Concat,
+            context: None,
+            normalization: Normalization::Raw,
+        };
+        assert_eq!(record.params(), raw_sha512);
+        assert_eq!(&*record.pepper, "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f");
+        // Not normalized: legacy clients hashed the bytes as typed
+        assert_eq!(&*record.input, "65cc81");
+        assert_eq!(
+            record.commitment,
+            "92ae19c08cd387548c2cacfbccfa05f6b51bfaa3d20b4f8bc9ff60725a595e688f7b7da4afa6188e2fbed8fb144e674bb3afceaf2e48af277521f850df7ca9db"
+        );
+        assert_eq!(record.created_at, None);
+        assert!(crate::verify(&record.commitment(), &record.secret().unwrap()));
+    }
+
+    #[test]
+    fn legacy_entries_must_be_hex_and_hold_a_pepper() {
+        assert!(SecretRecord::from_legacy("not hex").is_err());
+        assert!(SecretRecord::from_legacy("0011").is_err());
+    }
+
+    #[test]
+    fn legacy_files_load() {
+        let path = std::env::temp_dir().join(format!("concoin-legacy-{}.json", std::process::id()));
+        std::fs::write(&path, format!("[[\"{LEGACY}\"]]")).unwrap();
+        let loaded = load_data_from_json(&path, None);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), vec![SecretRecord::from_legacy(LEGACY).unwrap()]);
+    }
+}